@@ -0,0 +1,277 @@
+//! Animated GIF input and output, and animated WebP with `--features webp`.
+//!
+//! OpenCV's GIF support depends on how it was built and it cannot write GIFs at all,
+//! so frames are decoded and encoded with the `image` crate and only the masking runs on `Mat`s.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use image::{AnimationDecoder, Frame, RgbaImage};
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use opencv::core::{absdiff, mean, Rect};
+use opencv::prelude::*;
+use opencv::imgproc::{cvt_color, COLOR_BGRA2RGBA, COLOR_RGBA2BGRA};
+
+use crate::stamp::draw_stamp;
+use crate::{apply_mask, build_passes, check_all_matched, iou, mask_regions, stem_of, CodeMatch, MaskConfig, MaskMode, MaskMyNameError, Progress, RegionMatch};
+
+/// Mean absolute difference per channel below which two frames count as the same picture.
+const SIMILAR_FRAME_THRESHOLD: f64 = 1.0;
+/// Masked regions of consecutive frames overlapping by at least this intersection over union are the same region.
+const TRACK_IOU: f64 = 0.3;
+
+pub fn is_gif(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("gif"))
+        .unwrap_or(false)
+}
+
+pub fn is_webp(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("webp"))
+        .unwrap_or(false)
+}
+
+/// Whether `path` is masked frame by frame with `process_animation`: GIFs, and WebPs with `--features webp`.
+pub fn is_animation(path: &Path) -> bool {
+    is_gif(path) || (cfg!(feature = "webp") && is_webp(path))
+}
+
+/// What was matched in one frame of an animation.
+#[derive(Debug, Clone)]
+pub struct FrameMatches {
+    /// 0 for the first frame.
+    pub frame: usize,
+    pub matches: Vec<RegionMatch>,
+    pub codes: Vec<CodeMatch>,
+}
+
+/// Frame and match counts of one masked animation.
+#[derive(Debug, Clone, Default)]
+pub struct AnimationSummary {
+    pub frames: usize,
+    /// Frames with at least one match, reused masks included.
+    pub matched_frames: usize,
+    /// Regions matched, summed over every frame.
+    pub regions: usize,
+    /// Frames where masks were held over from earlier frames for `config.hold_frames`.
+    pub held_frames: usize,
+    /// The matches of every frame with one, in frame order. A frame masked like the one before it repeats its matches,
+    /// masks held over are not matches and are left out.
+    pub frame_matches: Vec<FrameMatches>,
+}
+
+/// `process_gif` or `process_webp` by the extension of `image_path`.
+/// `progress` is called for the regions of every frame that goes through OCR, one frame after another.
+pub fn process_animation(image_path: &Path, output_path: &str, target_strings: &[String], config: &MaskConfig,
+                         progress: Progress) -> Result<AnimationSummary, MaskMyNameError> {
+    #[cfg(feature = "webp")]
+    if is_webp(image_path) {
+        return process_webp(image_path, output_path, target_strings, config, progress);
+    }
+    process_gif(image_path, output_path, target_strings, config, progress)
+}
+
+/// A BGRA `Mat` of tightly packed RGBA `data`, `height` rows tall.
+fn rgba_to_mat(data: &[u8], height: u32) -> opencv::Result<Mat> {
+    let rgba = Mat::from_slice(data)?.reshape(4, height as i32)?;
+    let mut bgra: Mat = Default::default();
+    cvt_color(&rgba, &mut bgra, COLOR_RGBA2BGRA, 0)?;
+    Ok(bgra)
+}
+
+fn mat_to_rgba(image: &Mat) -> Result<RgbaImage, MaskMyNameError> {
+    let mut rgba: Mat = Default::default();
+    cvt_color(image, &mut rgba, COLOR_BGRA2RGBA, 0).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    let data = rgba.data_bytes().map_err(|_| MaskMyNameError::ImageWriteError())?.to_vec();
+    RgbaImage::from_raw(rgba.cols() as u32, rgba.rows() as u32, data).ok_or(MaskMyNameError::ImageWriteError())
+}
+
+fn is_similar(a: &Mat, b: &Mat) -> opencv::Result<bool> {
+    let mut diff: Mat = Default::default();
+    absdiff(a, b, &mut diff)?;
+    let channel_means = mean(&diff, &Mat::default())?;
+    let average = (0..4).map(|i| channel_means[i]).sum::<f64>() / a.channels() as f64;
+    Ok(average < SIMILAR_FRAME_THRESHOLD)
+}
+
+/// The last frame that went through OCR, kept unmasked so later frames can be compared with it.
+struct Reference {
+    image: Mat,
+    masked: Vec<(Rect, MaskMode)>,
+    matches: Vec<RegionMatch>,
+    codes: Vec<CodeMatch>,
+}
+
+/// A masked region followed across frames, with the number of frames since it was last masked.
+struct Track {
+    area: Rect,
+    mode: MaskMode,
+    missed: u32,
+}
+
+/// Follows the regions masked in this frame, `masked`, from the tracks of earlier frames and drops tracks missed for more
+/// than `hold_frames`. Returns the tracks missed this frame but still held, to be masked as well.
+fn update_tracks(tracks: &mut Vec<Track>, masked: &[(Rect, MaskMode)], hold_frames: u32) -> Vec<(Rect, MaskMode)> {
+    let new: Vec<(Rect, MaskMode)> = masked.iter().filter(|(area, _)| !tracks.iter().any(|t| iou(&t.area, area) >= TRACK_IOU)).copied().collect();
+    for track in tracks.iter_mut() {
+        match masked.iter().find(|(area, _)| iou(&track.area, area) >= TRACK_IOU) {
+            Some((area, mode)) => {
+                // follows the region as it moves.
+                track.area = *area;
+                track.mode = *mode;
+                track.missed = 0;
+            },
+            None => { track.missed += 1; }
+        }
+    }
+    tracks.retain(|t| t.missed <= hold_frames);
+    tracks.extend(new.into_iter().map(|(area, mode)| Track { area, mode, missed: 0 }));
+    tracks.iter().filter(|t| t.missed > 0).map(|t| (t.area, t.mode)).collect()
+}
+
+/// Masks `frames` of the animation at `image_path` in order, reusing and holding masks across frames as `config` asks,
+/// and counts frames and matches. Fails with `NoMatchingString` when no frame matched.
+fn mask_frames(image_path: &Path, frames: Vec<Mat>, target_strings: &[String], config: &MaskConfig,
+               progress: Progress) -> Result<(Vec<Mat>, AnimationSummary), MaskMyNameError> {
+    let mut passes = build_passes(target_strings, config)?;
+    let mut reference: Option<Reference> = None;
+    let mut summary = AnimationSummary { frames: frames.len(), ..Default::default() };
+    let mut output_frames = Vec::with_capacity(frames.len());
+    let mut matched_targets: Vec<String> = Vec::new();
+    let mut tracks: Vec<Track> = Vec::new();
+    for (index, image) in frames.into_iter().enumerate() {
+        let reusable = match &reference {
+            Some(r) if config.reuse_similar_frames => {
+                is_similar(&r.image, &image).map_err(|_| MaskMyNameError::ImageReadError())?
+            },
+            _ => false,
+        };
+        let (masked_image, masked_areas, matches, codes) = match (reusable, &reference) {
+            (true, Some(r)) => {
+                for (area, mode) in &r.masked {
+                    apply_mask(&image, *area, *mode, None, config, &mut Vec::new())?;
+                }
+                let mut image = image;
+                if let (Some(notice), true) = (&config.stamp, !r.masked.is_empty() || !r.matches.is_empty() || !r.codes.is_empty()) {
+                    let areas: Vec<Rect> = r.masked.iter().map(|(area, _)| *area).collect();
+                    draw_stamp(&mut image, notice, &areas)?;
+                }
+                (image, r.masked.clone(), r.matches.clone(), r.codes.clone())
+            },
+            _ => {
+                let original = image.try_clone().map_err(|_| MaskMyNameError::ImageReadError())?;
+                let label = format!("{}_frame{}", stem_of(image_path), index);
+                let masked = mask_regions(&mut passes, image, &label, config, &mut *progress)?;
+                matched_targets.extend(masked.matches.iter().map(|m| m.target.clone()));
+                let masked_areas: Vec<(Rect, MaskMode)> = masked.masked.into_iter().zip(masked.mask_modes).collect();
+                reference = Some(Reference { image: original, masked: masked_areas.clone(), matches: masked.matches.clone(), codes: masked.codes.clone() });
+                (masked.image, masked_areas, masked.matches, masked.codes)
+            }
+        };
+        if config.hold_frames > 0 {
+            let held = update_tracks(&mut tracks, &masked_areas, config.hold_frames);
+            for (area, mode) in &held {
+                apply_mask(&masked_image, *area, *mode, None, config, &mut Vec::new())?;
+            }
+            if !held.is_empty() {
+                summary.held_frames += 1;
+            }
+        }
+        if !matches.is_empty() || !codes.is_empty() {
+            summary.matched_frames += 1;
+            summary.regions += matches.len() + codes.len();
+            summary.frame_matches.push(FrameMatches { frame: index, matches, codes });
+        }
+        output_frames.push(masked_image);
+    }
+    if summary.matched_frames == 0 {
+        return Err(MaskMyNameError::NoMatchingString());
+    }
+    if config.require_all {
+        check_all_matched(&passes, matched_targets.iter().map(String::as_str))?;
+    }
+    Ok((output_frames, summary))
+}
+
+/// Masks every frame of a GIF and writes it back as an animated GIF with the original frame delays.
+/// Each frame's palette is rebuilt from its pixels, which is lossless as long as the frame keeps
+/// within 256 colours, as GIF frames do unless the mask colour adds a new one.
+pub fn process_gif(image_path: &Path, output_path: &str, target_strings: &[String], config: &MaskConfig,
+                   progress: Progress) -> Result<AnimationSummary, MaskMyNameError> {
+    let file = File::open(image_path).map_err(|_| MaskMyNameError::ImageReadError())?;
+    let decoder = GifDecoder::new(BufReader::new(file)).map_err(|_| MaskMyNameError::ImageReadError())?;
+    let frames = decoder.into_frames().collect_frames().map_err(|_| MaskMyNameError::ImageReadError())?;
+    let images = frames.iter().map(|frame| rgba_to_mat(frame.buffer().as_raw(), frame.buffer().height()))
+        .collect::<opencv::Result<Vec<Mat>>>().map_err(|_| MaskMyNameError::ImageReadError())?;
+    let (masked, summary) = mask_frames(image_path, images, target_strings, config, progress)?;
+    let output_frames = masked.iter().zip(&frames)
+        .map(|(image, frame)| Ok(Frame::from_parts(mat_to_rgba(image)?, frame.left(), frame.top(), frame.delay())))
+        .collect::<Result<Vec<Frame>, MaskMyNameError>>()?;
+    let file = File::create(output_path).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    encoder.encode_frames(output_frames).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    Ok(summary)
+}
+
+/// Masks every frame of a WebP, animated or not, and writes it back losslessly with the original frame timing.
+/// A still WebP is a single frame and comes out still.
+#[cfg(feature = "webp")]
+pub fn process_webp(image_path: &Path, output_path: &str, target_strings: &[String], config: &MaskConfig,
+                    progress: Progress) -> Result<AnimationSummary, MaskMyNameError> {
+    use webp_animation::{Decoder, Encoder};
+    let bytes = std::fs::read(image_path).map_err(|_| MaskMyNameError::ImageReadError())?;
+    let decoder = Decoder::new(&bytes).map_err(|_| MaskMyNameError::ImageReadError())?;
+    let (width, height) = decoder.dimensions();
+    // the decoder reports when each frame ends, the encoder wants when it starts.
+    let (mut images, mut ends) = (Vec::new(), Vec::new());
+    for frame in decoder {
+        images.push(rgba_to_mat(frame.data(), height).map_err(|_| MaskMyNameError::ImageReadError())?);
+        ends.push(frame.timestamp());
+    }
+    let (masked, summary) = mask_frames(image_path, images, target_strings, config, progress)?;
+    let mut encoder = Encoder::new((width, height)).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    let mut start = 0;
+    for (image, end) in masked.iter().zip(ends) {
+        encoder.add_frame(mat_to_rgba(image)?.as_raw(), start).map_err(|_| MaskMyNameError::ImageWriteError())?;
+        start = end;
+    }
+    let encoded = encoder.finalize(start).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    std::fs::write(output_path, &*encoded).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_follow_overlapping_regions_and_hold_missed_ones_for_hold_frames() {
+        let mut tracks = Vec::new();
+        let name = (Rect::new(10, 10, 100, 20), MaskMode::Bar);
+        assert!(update_tracks(&mut tracks, &[name], 2).is_empty());
+        // moved by 10 px: IoU 0.67, the same region, which the track follows along with its mask mode.
+        let moved = (Rect::new(20, 10, 100, 20), MaskMode::Blur);
+        assert!(update_tracks(&mut tracks, &[moved], 2).is_empty());
+        assert_eq!(tracks.len(), 1);
+        // no overlap, a second region.
+        let other = (Rect::new(200, 10, 100, 20), MaskMode::Bar);
+        assert!(update_tracks(&mut tracks, &[moved, other], 2).is_empty());
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(update_tracks(&mut tracks, &[other], 2), vec![moved]);
+        assert_eq!(update_tracks(&mut tracks, &[other], 2), vec![moved]);
+        assert!(update_tracks(&mut tracks, &[other], 2).is_empty());
+        assert_eq!(tracks.len(), 1);
+    }
+
+    #[test]
+    fn tracks_are_not_held_without_hold_frames() {
+        let mut tracks = Vec::new();
+        update_tracks(&mut tracks, &[(Rect::new(10, 10, 100, 20), MaskMode::Bar)], 0);
+        assert!(update_tracks(&mut tracks, &[], 0).is_empty());
+        assert!(tracks.is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+//! Detected regions and their OCR text as LabelMe or COCO annotations, for bootstrapping text detection datasets.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use opencv::core::Rect;
+use opencv::prelude::*;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{MaskedImage, MaskMyNameError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationFormat {
+    LabelMe,
+    Coco,
+}
+
+/// Label or category of OCR'd regions.
+const TEXT_LABEL: &str = "text";
+/// Label or category of QR codes, their payload taking the place of the text.
+const CODE_LABEL: &str = "qr-code";
+/// The LabelMe release whose schema is written.
+const LABELME_VERSION: &str = "5.2.1";
+
+#[derive(Serialize)]
+struct LabelMeShape {
+    label: &'static str,
+    points: [[i32; 2]; 2],
+    group_id: Option<i32>,
+    description: String,
+    shape_type: &'static str,
+    flags: Map<String, Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LabelMe {
+    version: &'static str,
+    flags: Map<String, Value>,
+    shapes: Vec<LabelMeShape>,
+    image_path: String,
+    image_data: Option<String>,
+    image_height: i32,
+    image_width: i32,
+}
+
+#[derive(Serialize)]
+struct CocoImage {
+    id: u32,
+    file_name: String,
+    width: i32,
+    height: i32,
+}
+
+#[derive(Serialize)]
+struct CocoAnnotation {
+    id: usize,
+    image_id: u32,
+    category_id: u32,
+    segmentation: Vec<[i32; 8]>,
+    area: i32,
+    bbox: [i32; 4],
+    iscrowd: u8,
+    utf8_string: String,
+}
+
+#[derive(Serialize)]
+struct CocoCategory {
+    id: u32,
+    name: &'static str,
+    supercategory: &'static str,
+}
+
+#[derive(Serialize)]
+struct Coco {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    categories: Vec<CocoCategory>,
+}
+
+/// Every OCR'd region with its text, then every QR code with its payload, each with its label.
+fn labelled_regions(masked: &MaskedImage) -> Vec<(Rect, &str, &'static str)> {
+    masked.regions.iter().map(|(area, text)| (*area, text.trim(), TEXT_LABEL))
+        .chain(masked.codes.iter().map(|code| (code.area, code.payload.as_str(), CODE_LABEL)))
+        .collect()
+}
+
+fn labelme(masked: &MaskedImage, image_path: String) -> LabelMe {
+    let shapes = labelled_regions(masked).into_iter().map(|(area, text, label)| LabelMeShape {
+        label,
+        points: [[area.x, area.y], [area.x + area.width, area.y + area.height]],
+        group_id: None,
+        description: text.to_string(),
+        shape_type: "rectangle",
+        flags: Map::new(),
+    }).collect();
+    LabelMe {
+        version: LABELME_VERSION,
+        flags: Map::new(),
+        shapes,
+        image_path,
+        image_data: None,
+        image_height: masked.image.rows(),
+        image_width: masked.image.cols(),
+    }
+}
+
+fn coco(masked: &MaskedImage, file_name: String) -> Coco {
+    let annotations = labelled_regions(masked).into_iter().enumerate().map(|(index, (area, text, label))| {
+        let (right, bottom) = (area.x + area.width, area.y + area.height);
+        CocoAnnotation {
+            id: index + 1,
+            image_id: 1,
+            category_id: if label == TEXT_LABEL { 1 } else { 2 },
+            segmentation: vec![[area.x, area.y, right, area.y, right, bottom, area.x, bottom]],
+            area: area.area(),
+            bbox: [area.x, area.y, area.width, area.height],
+            iscrowd: 0,
+            utf8_string: text.to_string(),
+        }
+    }).collect();
+    Coco {
+        images: vec![CocoImage { id: 1, file_name, width: masked.image.cols(), height: masked.image.rows() }],
+        annotations,
+        categories: vec![
+            CocoCategory { id: 1, name: TEXT_LABEL, supercategory: TEXT_LABEL },
+            CocoCategory { id: 2, name: CODE_LABEL, supercategory: CODE_LABEL },
+        ],
+    }
+}
+
+/// The file name of `image_path` when the annotation sits in the same directory, as LabelMe expects, its absolute path otherwise.
+fn image_reference(image_path: &Path, annotation_path: &Path) -> String {
+    let directory = |p: &Path| p.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new(".")).canonicalize().ok();
+    let reference = match (directory(image_path), directory(annotation_path), image_path.file_name()) {
+        (Some(a), Some(b), Some(name)) if a == b => { PathBuf::from(name) },
+        _ => { image_path.canonicalize().unwrap_or(image_path.to_path_buf()) }
+    };
+    reference.display().to_string()
+}
+
+/// Writes the regions of `masked`, found in `image_path`, to `annotation_path` as JSON.
+pub fn write_annotations(annotation_path: &str, image_path: &Path, masked: &MaskedImage,
+                         format: AnnotationFormat) -> Result<(), MaskMyNameError> {
+    let reference = image_reference(image_path, Path::new(annotation_path));
+    let json = match format {
+        AnnotationFormat::LabelMe => { serde_json::to_string_pretty(&labelme(masked, reference)) },
+        AnnotationFormat::Coco => { serde_json::to_string_pretty(&coco(masked, reference)) }
+    }.map_err(|e| MaskMyNameError::AnnotationWriteError(format!("{}: {}", annotation_path, e)))?;
+    fs::write(annotation_path, json + "\n").map_err(|e| MaskMyNameError::AnnotationWriteError(format!("{}: {}", annotation_path, e)))
+}
@@ -0,0 +1,114 @@
+//! ZIP archives of images as input, masked entries written to another archive or a directory tree.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use opencv::imgcodecs::imencode;
+use opencv::types::VectorOfu8;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{build_passes, check_coverage, decode, is_archive, mask_regions, orient, OcrPass, MaskConfig, MaskedImage, MaskMyNameError, Progress, IMAGE_EXTENSIONS};
+
+/// What became of one archive entry.
+pub enum EntryResult<'a> {
+    Masked(&'a MaskedImage),
+    /// `NoMatchingString` like any input without a match, or why the entry could not be masked.
+    Failed(&'a MaskMyNameError),
+    /// Not an image, or one that cannot be masked here, and why.
+    Skipped(&'static str),
+}
+
+/// Called once per file entry with its path inside the archive. `ControlFlow::Break` stops after that entry.
+pub type EntryReport<'a> = &'a mut dyn FnMut(&str, EntryResult) -> ControlFlow<()>;
+
+/// A ZIP file when `path` ends in `.zip`, a directory otherwise.
+enum Output {
+    Archive(Box<ZipWriter<File>>),
+    Directory(PathBuf),
+}
+
+impl Output {
+    fn write(&mut self, name: &str, enclosed: &Path, bytes: &[u8]) -> Result<(), MaskMyNameError> {
+        match self {
+            Output::Archive(writer) => {
+                // the images are compressed already.
+                writer.start_file(name, SimpleFileOptions::default().compression_method(CompressionMethod::Stored))
+                    .map_err(|e| MaskMyNameError::ArchiveError(format!("{}: {}", name, e)))?;
+                writer.write_all(bytes).map_err(|e| MaskMyNameError::ArchiveError(format!("{}: {}", name, e)))
+            },
+            Output::Directory(dir) => {
+                let path = dir.join(enclosed);
+                let written = path.parent().map(fs::create_dir_all).unwrap_or(Ok(())).and_then(|_| fs::write(&path, bytes));
+                written.map_err(|e| MaskMyNameError::ArchiveError(format!("{}: {}", path.display(), e)))
+            }
+        }
+    }
+}
+
+fn mask_entry(passes: &mut [OcrPass], name: &str, bytes: Vec<u8>, config: &MaskConfig, progress: Progress) -> Result<MaskedImage, MaskMyNameError> {
+    let (image, bytes) = decode(bytes)?;
+    let image = orient(image, &bytes.unwrap_or_default(), config)?;
+    let label = Path::new(name).file_stem().and_then(|s| s.to_str()).unwrap_or("entry");
+    let masked = mask_regions(passes, image, label, config, progress)?;
+    check_coverage(masked, config)
+}
+
+/// Masks every image entry of the ZIP at `archive_path`, nested directories included, and writes each masked one
+/// under the same path into `output_path`: a new ZIP when it ends in `.zip`, a directory tree otherwise.
+/// Entries without a match are left out, like any input without one. Animated GIFs are skipped.
+/// `progress` is called for the regions of each entry, an entry it stops fails with `MaskMyNameError::Cancelled`.
+pub fn mask_archive(archive_path: &Path, output_path: &Path, target_strings: &[String], config: &MaskConfig,
+                    progress: Progress, report: EntryReport) -> Result<(), MaskMyNameError> {
+    let archive_error = |e: String| MaskMyNameError::ArchiveError(format!("{}: {}", archive_path.display(), e));
+    let file = File::open(archive_path).map_err(|e| archive_error(e.to_string()))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| archive_error(e.to_string()))?;
+    let mut output = match is_archive(output_path) {
+        true => {
+            let file = File::create(output_path).map_err(|e| MaskMyNameError::ArchiveError(format!("{}: {}", output_path.display(), e)))?;
+            Output::Archive(Box::new(ZipWriter::new(file)))
+        },
+        false => { Output::Directory(output_path.to_path_buf()) }
+    };
+    let mut passes = build_passes(target_strings, config)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|e| archive_error(e.to_string()))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let extension = Path::new(&name).extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+        // a name reaching outside the output directory, e.g. through `..`, is never written.
+        let enclosed = match (entry.enclosed_name(), extension) {
+            (None, _) => { report(&name, EntryResult::Skipped("unsafe path")) },
+            (Some(_), None) => { report(&name, EntryResult::Skipped("not an image")) },
+            (Some(_), Some(ext)) if !IMAGE_EXTENSIONS.contains(&ext.as_str()) => { report(&name, EntryResult::Skipped("not an image")) },
+            (Some(_), Some(ext)) if ext == "gif" => { report(&name, EntryResult::Skipped("animated GIFs are not supported inside archives")) },
+            (Some(enclosed), Some(ext)) => {
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes).map_err(|e| archive_error(format!("{}: {}", name, e)))?;
+                let masked = mask_entry(&mut passes, &name, bytes, config, &mut *progress).and_then(|masked| {
+                    let mut encoded = VectorOfu8::new();
+                    match imencode(&format!(".{}", ext), &masked.image, &mut encoded, &Default::default()) {
+                        Ok(true) => {},
+                        _ => { return Err(MaskMyNameError::ImageWriteError()); }
+                    }
+                    output.write(&name, &enclosed, &encoded.to_vec())?;
+                    Ok(masked)
+                });
+                match &masked {
+                    Ok(masked) => { report(&name, EntryResult::Masked(masked)) },
+                    Err(e) => { report(&name, EntryResult::Failed(e)) }
+                }
+            }
+        };
+        if enclosed.is_break() {
+            break;
+        }
+    }
+    if let Output::Archive(writer) = output {
+        writer.finish().map_err(|e| MaskMyNameError::ArchiveError(format!("{}: {}", output_path.display(), e)))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! Colour space tagging of encoded outputs, which `imwrite` leaves untagged.
+
+pub(crate) const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Signature, then the IHDR chunk: length, type, 13 bytes of data and the CRC.
+const PNG_IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+pub(crate) const JPEG_SOI: &[u8] = &[0xff, 0xd8];
+const JPEG_APP0: u8 = 0xe0;
+const JPEG_APP2: u8 = 0xe2;
+const ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+    chunk
+}
+
+/// Every chunk of a PNG as (type, whole chunk including length and CRC).
+pub(crate) fn png_chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut offset = PNG_SIGNATURE.len();
+    while offset + 12 <= png.len() {
+        let length = u32::from_be_bytes([png[offset], png[offset + 1], png[offset + 2], png[offset + 3]]) as usize;
+        let end = offset + 12 + length;
+        if end > png.len() {
+            break;
+        }
+        chunks.push((&png[offset + 4..offset + 8], &png[offset..end]));
+        offset = end;
+    }
+    chunks
+}
+
+/// Every marker segment of a JPEG before the image data as (marker, whole segment including marker and length).
+pub(crate) fn jpeg_segments(jpeg: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut segments = Vec::new();
+    let mut offset = JPEG_SOI.len();
+    // start of scan: entropy-coded data follows, no more segments worth looking at.
+    while offset + 4 <= jpeg.len() && jpeg[offset] == 0xff && jpeg[offset + 1] != 0xda {
+        // the length counts its own two bytes, anything shorter is corrupt.
+        let length = u16::from_be_bytes([jpeg[offset + 2], jpeg[offset + 3]]) as usize;
+        let end = offset + 2 + length;
+        if length < 2 || end > jpeg.len() {
+            break;
+        }
+        segments.push((jpeg[offset + 1], &jpeg[offset..end]));
+        offset = end;
+    }
+    segments
+}
+
+/// Whether a segment from `jpeg_segments` is an APP2 segment holding (part of) an ICC profile.
+fn is_icc_segment(marker: u8, segment: &[u8]) -> bool {
+    marker == JPEG_APP2 && segment.get(4..).map(|data| data.starts_with(ICC_MARKER)).unwrap_or(false)
+}
+
+/// An APP1 Exif segment holding only ColorSpace = 1 (sRGB) in its Exif IFD.
+fn exif_srgb() -> Vec<u8> {
+    let mut tiff: Vec<u8> = b"MM\0\x2a\0\0\0\x08".to_vec();
+    // IFD0: one entry pointing to the Exif IFD right after it, at offset 8 + 2 + 12 + 4.
+    tiff.extend_from_slice(&[0, 1, 0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 26, 0, 0, 0, 0]);
+    // Exif IFD: ColorSpace, SHORT, 1.
+    tiff.extend_from_slice(&[0, 1, 0xa0, 0x01, 0, 3, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0]);
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+    segment.extend_from_slice(b"Exif\0\0");
+    segment.extend_from_slice(&tiff);
+    segment
+}
+
+/// The PNG with the `iCCP` chunk of `source` if it is a PNG that has one, an `sRGB` chunk otherwise, right after IHDR.
+fn tag_png(png: Vec<u8>, source: Option<&[u8]>) -> Vec<u8> {
+    if png.len() < PNG_IHDR_END || png_chunks(&png).iter().any(|(kind, _)| *kind == b"iCCP" || *kind == b"sRGB") {
+        return png;
+    }
+    let profile = source.filter(|s| s.starts_with(PNG_SIGNATURE))
+        .and_then(|s| png_chunks(s).into_iter().find(|(kind, _)| *kind == b"iCCP").map(|(_, chunk)| chunk.to_vec()));
+    // rendering intent 0, perceptual.
+    let chunk = profile.unwrap_or_else(|| png_chunk(b"sRGB", &[0]));
+    [&png[..PNG_IHDR_END], &chunk, &png[PNG_IHDR_END..]].concat()
+}
+
+/// The JPEG with the ICC segments of `source` if it is a JPEG that has them, an Exif sRGB tag otherwise,
+/// after SOI and the JFIF header.
+fn tag_jpeg(jpeg: Vec<u8>, source: Option<&[u8]>) -> Vec<u8> {
+    let segments = jpeg_segments(&jpeg);
+    if segments.iter().any(|(marker, segment)| is_icc_segment(*marker, segment)) {
+        return jpeg;
+    }
+    let profile: Vec<u8> = source.filter(|s| s.starts_with(JPEG_SOI)).map(|s| {
+        jpeg_segments(s).into_iter()
+            .filter(|(marker, segment)| is_icc_segment(*marker, segment))
+            .flat_map(|(_, segment)| segment.to_vec())
+            .collect()
+    }).unwrap_or_default();
+    let tag = if profile.is_empty() { exif_srgb() } else { profile };
+    let insert_at = JPEG_SOI.len() + segments.first().filter(|(marker, _)| *marker == JPEG_APP0).map(|(_, s)| s.len()).unwrap_or(0);
+    [&jpeg[..insert_at], &tag, &jpeg[insert_at..]].concat()
+}
+
+/// Tags an encoded PNG or JPEG as sRGB, or with the ICC profile of `source` when it is the same format and has one.
+/// Outputs that already carry a profile and other formats are returned unchanged.
+pub fn tag(encoded: Vec<u8>, source: Option<&[u8]>) -> Vec<u8> {
+    if encoded.starts_with(PNG_SIGNATURE) {
+        tag_png(encoded, source)
+    } else if encoded.starts_with(JPEG_SOI) {
+        tag_jpeg(encoded, source)
+    } else {
+        encoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::{Mat, Scalar, Vector, CV_8UC3};
+    use opencv::imgcodecs::{imdecode, imencode, IMREAD_UNCHANGED};
+    use opencv::prelude::*;
+    use super::*;
+
+    fn encoded(extension: &str) -> Vec<u8> {
+        let image = Mat::new_rows_cols_with_default(8, 12, CV_8UC3, Scalar::all(128.)).unwrap();
+        let mut buffer: Vector<u8> = Vector::new();
+        imencode(extension, &image, &mut buffer, &Vector::new()).unwrap();
+        buffer.to_vec()
+    }
+
+    #[test]
+    fn crc32_matches_the_png_reference() {
+        // the CRC every PNG ends with, over the IEND type and no data.
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(png_chunk(b"IEND", &[]), [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+    }
+
+    #[test]
+    fn truncated_inputs_yield_the_complete_chunks_and_segments_only() {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(png_chunk(b"sRGB", &[0]));
+        png.extend(png_chunk(b"IEND", &[]));
+        assert_eq!(png_chunks(&png).iter().map(|(kind, _)| *kind).collect::<Vec<_>>(), [b"sRGB", b"IEND"]);
+        assert_eq!(png_chunks(&png[..png.len() - 1]).len(), 1);
+        // a length running past the end.
+        assert!(png_chunks(&[PNG_SIGNATURE, &[0, 0, 0, 99, b'I', b'D', b'A', b'T', 0, 0, 0, 0][..]].concat()).is_empty());
+        let app0 = [0xff, JPEG_APP0, 0, 4, 0, 0];
+        let jpeg = [JPEG_SOI, &app0[..], &[0xff, JPEG_APP2, 0, 8, 0, 0][..]].concat();
+        assert_eq!(jpeg_segments(&jpeg).len(), 1);
+        // lengths below 2 and APP2 segments too short for the ICC marker, which must not panic.
+        assert!(jpeg_segments(&[JPEG_SOI, &[0xff, JPEG_APP2, 0, 1, 0, 0][..]].concat()).is_empty());
+        let short = [JPEG_SOI, &[0xff, JPEG_APP2, 0, 2][..]].concat();
+        assert_eq!(jpeg_segments(&short).len(), 1);
+        assert!(tag_jpeg(short, None).windows(4).any(|w| w == b"Exif"));
+    }
+
+    #[test]
+    fn tagged_outputs_still_decode() {
+        let png = tag_png(encoded(".png"), None);
+        assert!(png_chunks(&png).iter().any(|(kind, _)| *kind == b"sRGB"));
+        let jpeg = tag_jpeg(encoded(".jpg"), None);
+        assert!(jpeg_segments(&jpeg).iter().any(|(marker, segment)| *marker == 0xe1 && segment[4..].starts_with(b"Exif")));
+        for bytes in [png, jpeg] {
+            let decoded = imdecode(&Vector::<u8>::from_slice(&bytes), IMREAD_UNCHANGED).unwrap();
+            assert_eq!((decoded.cols(), decoded.rows()), (12, 8));
+        }
+    }
+}
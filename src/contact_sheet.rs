@@ -0,0 +1,68 @@
+//! Several masked images tiled into one montage, for checking a batch at a glance.
+
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use opencv::core::{hconcat, vconcat, Rect, Scalar, Size, CV_8UC3};
+use opencv::imgproc::{resize, INTER_AREA};
+use opencv::prelude::*;
+use opencv::types::VectorOfMat;
+
+use crate::{build_passes, load_image, mask_regions, stem_of, to_8bit, to_bgr, MaskConfig, MaskMyNameError};
+
+/// Scales `image` to fit inside `tile` and centres it on a black tile of exactly that size.
+fn fit_tile(image: &Mat, tile: Size, interpolation: i32) -> opencv::Result<Mat> {
+    let scale = (tile.width as f64 / image.cols() as f64).min(tile.height as f64 / image.rows() as f64);
+    let size = Size::new(((image.cols() as f64 * scale) as i32).clamp(1, tile.width),
+                         ((image.rows() as f64 * scale) as i32).clamp(1, tile.height));
+    let mut resized: Mat = Default::default();
+    resize(image, &mut resized, size, 0., 0., interpolation)?;
+    let canvas = Mat::new_rows_cols_with_default(tile.height, tile.width, CV_8UC3, Scalar::all(0.))?;
+    let mut target = Mat::roi(&canvas, Rect::new((tile.width - size.width) / 2, (tile.height - size.height) / 2,
+                                                 size.width, size.height))?;
+    resized.copy_to(&mut target)?;
+    Ok(canvas)
+}
+
+/// Rows of `columns` tiles are joined with hconcat, then stacked with vconcat.
+/// The last row is padded with blank tiles so every row has the same width.
+fn compose(tiles: Vec<Mat>, columns: usize, tile: Size) -> opencv::Result<Mat> {
+    let mut rows = VectorOfMat::new();
+    for chunk in tiles.chunks(columns) {
+        let mut row_tiles = VectorOfMat::new();
+        for t in chunk {
+            row_tiles.push(t.try_clone()?);
+        }
+        for _ in chunk.len()..columns {
+            row_tiles.push(Mat::new_rows_cols_with_default(tile.height, tile.width, CV_8UC3, Scalar::all(0.))?);
+        }
+        let mut row: Mat = Default::default();
+        hconcat(&row_tiles, &mut row)?;
+        rows.push(row);
+    }
+    let mut sheet: Mat = Default::default();
+    vconcat(&rows, &mut sheet)?;
+    Ok(sheet)
+}
+
+/// Masks every image and tiles the results, `columns` per row, each scaled into a `tile` sized cell.
+/// Images without a match are still shown, unmasked, so the sheet covers the whole batch.
+/// Returns the sheet and the number of regions matched in each image, in the order of `image_paths`.
+pub fn contact_sheet(image_paths: &[PathBuf], target_strings: &[String], config: &MaskConfig,
+                     columns: usize, tile: Size) -> Result<(Mat, Vec<usize>), MaskMyNameError> {
+    if image_paths.is_empty() {
+        return Err(MaskMyNameError::InvalidArgument("no images for the contact sheet".to_string()));
+    }
+    let mut passes = build_passes(target_strings, config)?;
+    let mut tiles = Vec::with_capacity(image_paths.len());
+    let mut tile_matches = Vec::with_capacity(image_paths.len());
+    for image_path in image_paths {
+        let image = load_image(image_path, config)?;
+        let masked = mask_regions(&mut passes, image, stem_of(image_path), config, &mut |_, _| ControlFlow::Continue(()))?;
+        tile_matches.push(masked.matches.len());
+        let tile_image = to_bgr(&to_8bit(&masked.image)?).and_then(|bgr| fit_tile(&bgr, tile, config.interpolation.unwrap_or(INTER_AREA)))
+            .map_err(|e| MaskMyNameError::ContactSheetError(e.message))?;
+        tiles.push(tile_image);
+    }
+    let sheet = compose(tiles, columns.max(1), tile).map_err(|e| MaskMyNameError::ContactSheetError(e.message))?;
+    Ok((sheet, tile_matches))
+}
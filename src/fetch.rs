@@ -0,0 +1,18 @@
+//! Downloading http(s) inputs, only built with the `url` feature.
+
+use std::time::Duration;
+
+use crate::MaskMyNameError;
+
+pub fn fetch(url: &str, timeout: Duration) -> Result<Vec<u8>, MaskMyNameError> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| MaskMyNameError::FetchError(e.to_string()))?;
+    let response = client.get(url).send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| MaskMyNameError::FetchError(e.to_string()))?;
+    response.bytes()
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| MaskMyNameError::FetchError(e.to_string()))
+}
@@ -0,0 +1,74 @@
+//! Review of the proposed masks in a highgui window before they are applied.
+
+use std::sync::{Arc, Mutex};
+use opencv::core::{Point, Rect, Scalar};
+use opencv::highgui::{destroy_window, imshow, named_window, set_mouse_callback, wait_key, EVENT_LBUTTONDOWN, WINDOW_AUTOSIZE};
+use opencv::imgproc::{put_text, rectangle, FONT_HERSHEY_SIMPLEX, LINE_8};
+use opencv::prelude::*;
+
+use crate::{detection_input, MaskMyNameError};
+
+const WINDOW: &str = "mask-my-name: click to toggle, a/r all, enter to apply, esc to cancel";
+const KEY_ENTER: i32 = 13;
+const KEY_SPACE: i32 = 32;
+const KEY_ESC: i32 = 27;
+
+fn contains(area: &Rect, x: i32, y: i32) -> bool {
+    x >= area.x && x < area.x + area.width && y >= area.y && y < area.y + area.height
+}
+
+/// Outlines accepted regions in green and rejected ones in red, numbered in detection order.
+fn draw(image: &Mat, proposed: &[Rect], accepted: &[bool]) -> opencv::Result<Mat> {
+    let mut canvas = image.try_clone()?;
+    for (index, (area, accept)) in proposed.iter().zip(accepted).enumerate() {
+        let color = if *accept { Scalar::new(0., 200., 0., 0.) } else { Scalar::new(0., 0., 230., 0.) };
+        rectangle(&mut canvas, *area, color, 2, LINE_8, 0)?;
+        put_text(&mut canvas, &(index + 1).to_string(), Point::new(area.x, (area.y - 4).max(12)),
+                 FONT_HERSHEY_SIMPLEX, 0.5, color, 1, LINE_8, false)?;
+    }
+    Ok(canvas)
+}
+
+/// Shows `proposed` over `image` and returns the regions the user kept, all accepted to begin with.
+/// Escape fails with `MaskMyNameError::Cancelled` so nothing gets written.
+pub fn review(image: &Mat, proposed: &[Rect]) -> Result<Vec<Rect>, MaskMyNameError> {
+    if proposed.is_empty() {
+        return Ok(Vec::new());
+    }
+    let gui_error = |e: opencv::Error| MaskMyNameError::InteractiveError(e.message);
+    let display = detection_input(image)?;
+    let accepted = Arc::new(Mutex::new(vec![true; proposed.len()]));
+    named_window(WINDOW, WINDOW_AUTOSIZE).map_err(gui_error)?;
+    let clicked = Arc::clone(&accepted);
+    let areas = proposed.to_vec();
+    set_mouse_callback(WINDOW, Some(Box::new(move |event, x, y, _flags| {
+        if event != EVENT_LBUTTONDOWN {
+            return;
+        }
+        if let Ok(mut accepted) = clicked.lock() {
+            for (area, accept) in areas.iter().zip(accepted.iter_mut()) {
+                if contains(area, x, y) {
+                    *accept = !*accept;
+                }
+            }
+        }
+    }))).map_err(gui_error)?;
+    let result = loop {
+        let current = accepted.lock().map(|a| a.clone()).unwrap_or_default();
+        imshow(WINDOW, &draw(&display, proposed, &current).map_err(gui_error)?).map_err(gui_error)?;
+        match wait_key(30).map_err(gui_error)? {
+            KEY_ENTER | KEY_SPACE => {
+                break Ok(proposed.iter().zip(&current).filter(|(_, accept)| **accept).map(|(area, _)| *area).collect());
+            },
+            KEY_ESC => { break Err(MaskMyNameError::Cancelled()); },
+            key if key == 'a' as i32 || key == 'r' as i32 => {
+                if let Ok(mut accepted) = accepted.lock() {
+                    accepted.iter_mut().for_each(|accept| *accept = key == 'a' as i32);
+                }
+            },
+            _ => {}
+        }
+    };
+    destroy_window(WINDOW).map_err(gui_error)?;
+    result
+}
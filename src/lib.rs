@@ -0,0 +1,2607 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+use std::ffi::{CStr, CString, c_int};
+use thiserror::Error;
+use opencv::core::{absdiff, bitwise_and, bitwise_or, count_non_zero, extract_channel, hconcat, in_range, mean, mean_std_dev, merge, normalize, split, randu, NORM_MINMAX, set_rng_seed, BORDER_CONSTANT, BORDER_DEFAULT, CV_8UC1, CV_8UC3, CV_8UC4, Point, Point2f, Rect, Scalar, Size, CV_16S, CV_16U, CV_32F, CV_64F, CV_8S, CV_8U};
+use opencv::dnn::TextDetectionModel_EAST;
+use unicode_script::{Script, UnicodeScript};
+use opencv::features2d::MSER;
+use opencv::objdetect::QRCodeDetector;
+use opencv::photo::{inpaint, INPAINT_TELEA};
+use opencv::prelude::*;
+use opencv::imgcodecs::{imdecode, imread, IMREAD_UNCHANGED, imwrite};
+use opencv::imgproc::{calc_hist, canny, circle, threshold, COLOR_BGR2GRAY, THRESH_BINARY, THRESH_OTSU, convex_hull, fill_convex_poly, gaussian_blur, rectangle, FILLED, LINE_8, COLOR_BGR2BGRA, COLOR_BGR2HSV, COLOR_BGRA2BGR, COLOR_GRAY2BGR, cvt_color, dilate, MORPH_RECT, get_structuring_element, morphology_default_border_value, morphology_ex, resize, INTER_AREA, INTER_CUBIC, INTER_NEAREST, MORPH_CLOSE, find_contours, RETR_EXTERNAL, bounding_rect, CHAIN_APPROX_SIMPLE};
+use opencv::types::{VectorOff32, VectorOfi32, VectorOfMat, VectorOfPoint, VectorOfRect, VectorOfRotatedRect, VectorOfString, VectorOfu8, VectorOfVectorOfPoint};
+use tesseract_plumbing::{TessBaseApi, Text};
+use tesseract_plumbing::tesseract_sys::{TessBaseAPICreate, TessBaseAPIDelete, TessBaseAPIGetAvailableLanguagesAsVector, TessBaseAPIInit3, TessDeleteTextArray};
+
+pub mod animation;
+pub mod annotations;
+#[cfg(feature = "zip")]
+pub mod archive;
+pub mod color_profile;
+pub mod contact_sheet;
+pub mod manifest;
+pub mod mask_export;
+pub mod phonetic;
+pub mod redaction_log;
+pub mod segment;
+pub mod stamp;
+pub mod svg;
+pub mod tune;
+#[cfg(feature = "url")]
+mod fetch;
+#[cfg(feature = "interactive")]
+mod interactive;
+mod orientation;
+#[cfg(feature = "script")]
+mod script;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MaskMyNameError {
+    #[error("Read image failed.")]
+    ImageReadError(),
+    #[error("Mask text failed: {0}")]
+    MaskTextError(String),
+    #[error("Failed to initialize Tesseract with language \"{0}\". check the language pack is installed (see `mask-my-name list-langs`).")]
+    TessInitError(String),
+    #[error("Failed to initialize Tesseract from tessdata {0}. check the directory exists and holds at least one language pack.")]
+    TessdataError(String),
+    #[error("Failed to get text from Tesseract.")]
+    TessGetTextError(),
+    #[error("Failed to create black bar Mat: {0}")]
+    MaskingBarCreationError(String),
+    #[error("No matching string found.")]
+    NoMatchingString(),
+    #[error("Failed to create ROI: {0}")]
+    RoiCreationError(String),
+    #[error("Failed to set image to Tesseract.")]
+    TessSetImageError(),
+    #[error("Write image failed.")]
+    ImageWriteError(),
+    #[error("Failed to read input directory.")]
+    InputDirReadError(),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Failed to run DNN text detector: {0}")]
+    DnnDetectionError(String),
+    #[error("Failed to convert image to 8-bit: {0}")]
+    DepthConversionError(String),
+    #[error("Failed to compute histogram: {0}")]
+    HistogramError(String),
+    #[error("Region {0} is outside the image.")]
+    RegionOutOfBounds(String),
+    #[error("Failed to fetch image: {0}")]
+    FetchError(String),
+    #[error("Failed to blur image: {0}")]
+    BlurError(String),
+    #[error("Cancelled.")]
+    Cancelled(),
+    #[error("Failed to compose contact sheet: {0}")]
+    ContactSheetError(String),
+    #[error("Targets not found: {0}")]
+    MissingTargetsError(String),
+    #[error("Tesseract rejected variable {0}.")]
+    TessVariableError(String),
+    #[error("Failed to create mask layer: {0}")]
+    MaskLayerError(String),
+    #[error("Interactive review failed: {0}")]
+    InteractiveError(String),
+    #[error("Failed to read word list {0}.")]
+    WordlistReadError(String),
+    #[error("Output {0} already exists.")]
+    OutputExists(String),
+    #[error("Target still readable after masking in region(s) {0}.")]
+    VerificationError(String),
+    #[error("Failed to detect QR codes: {0}")]
+    QrDetectionError(String),
+    #[error("Failed to write CSV log: {0}")]
+    CsvWriteError(String),
+    #[error("Failed to write the audit database {0}")]
+    DbWriteError(String),
+    #[error("Empty target would mask every detected region. give a name, or pass --redact-all to mask everything.")]
+    EmptyTarget(),
+    #[error("Failed to write SVG overlay {0}.")]
+    SvgWriteError(String),
+    #[error("Failed to compose the comparison image: {0}")]
+    CompareError(String),
+    #[error("Failed to compute the difference image: {0}")]
+    DiffError(String),
+    #[error("Failed to inpaint a region: {0}")]
+    InpaintError(String),
+    #[error("Failed to preprocess a region for OCR: {0}")]
+    OcrPreprocessError(String),
+    #[error("Failed to draw the stamp: {0}")]
+    StampError(String),
+    #[error("Failed to read or write archive {0}")]
+    ArchiveError(String),
+    #[error("Failed to write annotations {0}")]
+    AnnotationWriteError(String),
+    #[error("Failed to write segmentation {0}")]
+    MaskExportError(String),
+    #[error("Failed to apply EXIF orientation: {0}")]
+    OrientationError(String),
+    #[error("Failed to read labels {0}")]
+    LabelsReadError(String),
+    #[error("Failed to read manifest {0}")]
+    ManifestReadError(String),
+    #[error("Failed to read or update resume state {0}.")]
+    ResumeStateError(String),
+    #[error("Match script failed: {0}")]
+    ScriptError(String),
+    #[error("Failed to upscale region for another OCR pass: {0}")]
+    RescanError(String),
+}
+
+/// Where candidate text regions come from before OCR.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Detector {
+    /// HSV threshold + dilate + contours. Needs no model file.
+    Contour,
+    /// OpenCV's EAST text detector, loaded from the given `.pb` model.
+    Dnn(PathBuf),
+    /// Maximally stable extremal regions, i.e. glyphs of any colour standing out from their surroundings, joined into lines.
+    /// Needs no model file and copes with anti-aliased or gradient text the HSV threshold misses.
+    Mser,
+    /// Every blob inside `MaskConfig::hsv_lower` / `hsv_upper`, masked as is without OCR.
+    ColorRange,
+    /// Regions found elsewhere, e.g. by an external model, in image coordinates. Each must lie inside the image.
+    Boxes(Vec<Rect>),
+}
+
+impl Detector {
+    /// The name the CLI knows it by.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Detector::Contour => { "contour" },
+            Detector::Dnn(_) => { "dnn" },
+            Detector::Mser => { "mser" },
+            Detector::ColorRange => { "color-range" },
+            Detector::Boxes(_) => { "boxes" }
+        }
+    }
+}
+
+/// How a matched region is hidden.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskMode {
+    /// Solid bar in the brightest colour of the image depth.
+    Bar,
+    /// Solid bar in the mean colour of a thin ring around the region.
+    AutoColor,
+    /// Gaussian blur of the region, see `MaskConfig::blur_strength`.
+    Blur,
+    /// Mosaic of `MaskConfig::pixel_size` blocks.
+    Pixelate,
+    /// `Blur`, then `Pixelate` of the blurred region.
+    Strong,
+    /// Uniform random pixel values from OpenCV's RNG, see `MaskConfig::seed`. Nothing of the original is left.
+    Noise,
+    /// The region reconstructed from the pixels around it, see `MaskConfig::inpaint_method`. 8-bit images only.
+    Inpaint,
+}
+
+/// What to do about a bar that barely stands out from the pixels around it, e.g. a white bar on white paper.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContrastCheck {
+    /// Mask as asked and report the region in `MaskedImage::low_contrast`.
+    Warn,
+    /// Report it too, but fill it in black or white, whichever contrasts more.
+    Switch,
+}
+
+/// Square tiles for detecting text in images too large to convert in one go.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tiling {
+    /// Tile edge in pixels.
+    pub size: i32,
+    /// Pixels shared by neighbouring tiles. Should exceed the tallest text line so no line is only ever seen cut.
+    pub overlap: i32,
+}
+
+/// A band of the image by its position, see `MaskConfig::mask_zone`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskZone {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    /// The middle of both axes.
+    Center,
+}
+
+/// What each region's crop goes through before Tesseract reads it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OcrPreprocess {
+    Grayscale,
+    /// Greyscale, then black and white at the threshold Otsu's method picks for the crop.
+    Otsu,
+    /// Greyscale, then stretched so the darkest pixel is black and the brightest white.
+    Stretch,
+}
+
+/// Which regions stay sharp when the rest of the image is blurred.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SharpRegions {
+    Matched,
+    Detected,
+}
+
+/// How OCR text is compared with the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    /// The normalized text contains the target.
+    Substring,
+    /// Digits only: separators in phone numbers or IDs are ignored on both sides.
+    Numeric,
+    /// The whole normalized text equals the target.
+    Exact,
+    /// The normalized text starts with the target, e.g. a label like `id:`.
+    Prefix,
+    /// The normalized text ends with the target.
+    Suffix,
+    /// Best edit-distance similarity (0.0 - 1.0) against every target, masked when it reaches the threshold.
+    Fuzzy(f64),
+    /// The words of the target appear in the text by their Soundex codes, see `phonetic::sounds_like`.
+    Phonetic,
+    /// The target is a pattern found anywhere in the normalized text, see `glob_matches`.
+    Glob,
+}
+
+/// A target as given by the user, with the spellings it is matched by.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    pub variants: Vec<String>,
+}
+
+/// A region that got masked and the target that won it.
+#[derive(Debug, Clone)]
+pub struct RegionMatch {
+    pub area: Rect,
+    pub text: String,
+    pub target: String,
+    /// 1.0 for exact modes, the similarity in fuzzy mode.
+    pub score: f64,
+}
+
+/// A QR code found by `mask_qr` and what it decodes to, empty when it could not be decoded.
+#[derive(Debug, Clone)]
+pub struct CodeMatch {
+    pub area: Rect,
+    pub payload: String,
+}
+
+/// How many of the targets an image matched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coverage {
+    /// Every target matched at least once.
+    Full,
+    /// Some targets matched, others did not.
+    Partial,
+    /// No target matched and no QR code was found.
+    NoMatch,
+}
+
+pub struct MaskedImage {
+    pub image: Mat,
+    /// The image as loaded, before masking, with `config.keep_original`.
+    pub original: Option<Mat>,
+    pub matches: Vec<RegionMatch>,
+    /// Every region that was masked. Differs from `matches` with `invert_mask`.
+    pub masked: Vec<Rect>,
+    /// The mask mode each of `masked` was hidden with, in the same order, see `MaskConfig::target_mask_modes`.
+    pub mask_modes: Vec<MaskMode>,
+    /// Every region that was looked at and what OCR read there, in detection order. The text is empty with `config.masks_everything()`.
+    pub regions: Vec<(Rect, String)>,
+    pub codes: Vec<CodeMatch>,
+    /// Regions matched per target, in target order, zero for the ones that were not found.
+    pub target_counts: Vec<(String, usize)>,
+    /// Detected regions each detector contributed after merging, before zone filtering. Empty with a single detector.
+    pub detector_counts: Vec<(String, usize)>,
+    /// Masks whose bar colour is below `MIN_CONTRAST` against their surroundings, with `config.contrast_check`.
+    pub low_contrast: Vec<Rect>,
+    /// How far `config.relax_attempts` loosened detection, `None` when the first attempt was kept.
+    pub relaxed: Option<Relaxation>,
+    /// `max_matches` was reached and the remaining regions were not looked at.
+    pub capped: bool,
+    /// OCR scans repeated on an upscaled crop for `rescan_below`, one per pass that needed it.
+    pub rescanned: usize,
+    /// Regions dropped before OCR for `min_region_contrast`.
+    pub flat_regions: usize,
+    /// OCR scans repeated after a failure for `config.ocr_retries`.
+    pub ocr_retried: usize,
+    pub timings: Timings,
+}
+
+/// Wall time per stage of one image, summed over tiles. `mask_text` stays zero for detectors other than contours,
+/// whose whole run counts as `find_regions`. `write` is left to the caller.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timings {
+    pub load: Duration,
+    pub mask_text: Duration,
+    pub find_regions: Duration,
+    pub ocr: Duration,
+    pub write: Duration,
+}
+
+impl Timings {
+    pub fn add(&mut self, other: &Timings) {
+        self.load += other.load;
+        self.mask_text += other.mask_text;
+        self.find_regions += other.find_regions;
+        self.ocr += other.ocr;
+        self.write += other.write;
+    }
+}
+
+/// The loosened contour detection settings of a retry after finding no match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Relaxation {
+    /// 1 for the first retry.
+    pub attempt: u32,
+    /// Added to the hue, saturation and value upper bounds of every HSV range.
+    pub hsv_widened_by: f64,
+    /// Minimum region height in pixels.
+    pub min_height: i32,
+}
+
+impl MaskedImage {
+    pub fn coverage(&self) -> Coverage {
+        if self.matches.is_empty() && self.codes.is_empty() {
+            Coverage::NoMatch
+        } else if self.target_counts.iter().all(|(_, count)| *count > 0) {
+            Coverage::Full
+        } else {
+            Coverage::Partial
+        }
+    }
+
+    pub fn missing_targets(&self) -> Vec<&str> {
+        self.target_counts.iter().filter(|(_, count)| *count == 0).map(|(target, _)| target.as_str()).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MaskConfig {
+    /// Tesseract language code(s), e.g. `eng` or `eng+jpn`.
+    pub lang: String,
+    /// Directory holding the `.traineddata` files. Tesseract's own default is used when unset.
+    pub tessdata_dir: Option<PathBuf>,
+    pub detector: Detector,
+    /// More text detectors whose regions are merged with `detector`'s, see `detect_regions`. Not `ColorRange`.
+    pub extra_detectors: Vec<Detector>,
+    /// Convert high bit depth input to 8-bit for the whole run, output included.
+    pub force_8bit: bool,
+    /// Keep a copy of the image before masking in `MaskedImage::original`, for `side_by_side`.
+    pub keep_original: bool,
+    /// When non-empty, only detected regions intersecting one of these are OCR'd.
+    pub include_zones: Vec<Rect>,
+    /// Part of the image to detect, read and mask in, the rest is left untouched. Must lie inside the image.
+    pub crop: Option<Rect>,
+    /// Only regions centred in this band are OCR'd, like `include_zones`. It spans `mask_zone_fraction` of the image
+    /// along its axis, or of both axes for `MaskZone::Center`.
+    pub mask_zone: Option<MaskZone>,
+    /// 0.0 - 1.0.
+    pub mask_zone_fraction: f64,
+    /// Extra attempts for a region when Tesseract fails to return text.
+    pub ocr_retries: u32,
+    pub mask_mode: MaskMode,
+    /// `(target, mode)` pairs: regions that matched `target` are hidden with `mode` instead of `mask_mode`.
+    pub target_mask_modes: Vec<(String, MaskMode)>,
+    /// Compare each bar with its surroundings, `MaskMode::Bar` only: a blended bar is what `AutoColor` is for.
+    pub contrast_check: Option<ContrastCheck>,
+    /// Notice drawn into a corner of every image that had something masked or matched.
+    pub stamp: Option<stamp::Stamp>,
+    /// Save every region's original pixels here for auditing.
+    pub crops_dir: Option<PathBuf>,
+    /// Only save the regions that matched.
+    pub crops_matched_only: bool,
+    pub match_mode: MatchMode,
+    /// Mask the detected regions that did not match and leave the matches visible.
+    pub invert_mask: bool,
+    /// In animations, reuse the previous OCR'd frame's masks for frames that look the same.
+    pub reuse_similar_frames: bool,
+    /// In animations, keep masking a region for this many frames after it was last masked, so masks do not
+    /// flicker when OCR misses a frame.
+    pub hold_frames: u32,
+    /// Contour regions must be taller than image height / this.
+    pub min_height_divisor: i32,
+    /// 0 to `MAX_SENSITIVITY`, scaling the default dark-pixel bound and the dilation passes of contour detection,
+    /// higher finding more. `sensitivity_min_height_divisor` gives the matching divisor.
+    pub sensitivity: u8,
+    /// Absolute minimum region height in pixels, overrides `min_height_divisor`.
+    pub min_height: Option<i32>,
+    /// Retries of an image without a match, each with wider HSV bounds and half the minimum height of the one before.
+    pub relax_attempts: u32,
+    /// Contour regions wider than this many times their height are dropped.
+    pub max_aspect_ratio: f64,
+    /// Contour regions with a bounding box smaller than this many pixels are dropped.
+    pub min_region_area: Option<i32>,
+    /// Contour regions with a bounding box larger than this many pixels are dropped.
+    pub max_region_area: Option<i32>,
+    /// HSV bounds of the pixels detection keeps, in OpenCV's 0-180 / 0-255 / 0-255 ranges.
+    /// Text detection defaults to dark pixels, with the value bound depending on the image height.
+    pub hsv_lower: Option<Scalar>,
+    pub hsv_upper: Option<Scalar>,
+    /// Several `(lower, upper)` HSV ranges whose pixels are united before dilation, replacing `hsv_lower`/`hsv_upper`.
+    pub hsv_ranges: Vec<(Scalar, Scalar)>,
+    /// Kernel of a morphological closing of the threshold mask before dilation, off when unset.
+    pub close_kernel: Option<Size>,
+    /// `MORPH_RECT`, `MORPH_ELLIPSE` or `MORPH_CROSS`, the shape of the closing and dilation kernels.
+    pub kernel_shape: i32,
+    /// `CHAIN_APPROX_SIMPLE` or `CHAIN_APPROX_NONE` for tracing regions. Only their bounding boxes are used,
+    /// which both give alike, so the default stores just the corners of each contour.
+    pub contour_approx: i32,
+    /// Limit for downloading an http(s) input.
+    pub fetch_timeout: Duration,
+    /// Gaussian kernel size for blurring, odd.
+    pub blur_strength: i32,
+    /// Block edge in pixels for the pixelate and strong mask modes.
+    pub pixel_size: i32,
+    /// `INPAINT_TELEA` or `INPAINT_NS` for the inpaint mask mode.
+    pub inpaint_method: i32,
+    /// OpenCV interpolation for every resize, instead of each one's own default
+    /// (`INTER_AREA` when shrinking, `INTER_NEAREST` when enlarging a mosaic).
+    pub interpolation: Option<i32>,
+    /// Blur the whole image except these regions instead of masking anything.
+    pub blur_background: Option<SharpRegions>,
+    /// Fail unless every target matched at least once.
+    pub require_all: bool,
+    /// Tesseract variables set by name before scanning.
+    pub tess_vars: Vec<(String, String)>,
+    /// Height of the masked area relative to the detected region, kept centred on it.
+    pub bar_scale: f64,
+    /// Detect tile by tile instead of on the whole image at once.
+    pub tiling: Option<Tiling>,
+    /// Let the user accept or reject each mask in a window before it is applied.
+    pub interactive: bool,
+    /// Target variants shorter than this many characters are ignored.
+    pub min_token_length: usize,
+    /// Lowercase groups of characters treated as the same when matching, e.g. `DEFAULT_CONFUSABLES`. Empty to compare as read.
+    pub confusables: Vec<String>,
+    /// Split run-together words in the OCR text before matching, see `segment::segment`.
+    pub segment_words: bool,
+    /// Drop digits from the OCR text before matching, for stray page numbers and the like. Targets are left as they are.
+    pub strip_digits: bool,
+    /// Lowercased dictionary for `segment_words`. Without one only camelCase is split.
+    pub wordlist: HashSet<String>,
+    /// Extra `(language, target)` pairs, each language OCR'd in its own pass and matched only against its own targets.
+    pub lang_targets: Vec<(String, String)>,
+    /// Mask every region whose text has a character of one of these Unicode scripts, whatever the targets.
+    pub mask_scripts: Vec<Script>,
+    /// Mask every detected region without OCR, whatever the targets.
+    pub redact_all: bool,
+    /// Turn images upright according to their EXIF orientation before detection. The output is written upright and untagged.
+    pub auto_orient: bool,
+    /// Stop after this many matches in an image, leaving the remaining regions as they are.
+    pub max_matches: Option<usize>,
+    /// Also mask QR codes, independently of the text.
+    pub mask_qr: bool,
+    /// OCR the masked regions again and fail if a target can still be read.
+    pub verify: bool,
+    /// Seed for OpenCV's RNG, reset at the start of every image so results do not depend on batch order.
+    pub seed: Option<i32>,
+    /// Pixels added around each detected region for OCR only, clipped to the image, so clipped ascenders
+    /// and descenders are read in full. The mask keeps the detected size.
+    pub ocr_margin: i32,
+    /// OCR a region again from a `RESCAN_SCALE` times upscaled crop when Tesseract's mean confidence (0-100) is below this.
+    pub rescan_below: Option<i32>,
+    /// OCR each crop after this, the raw crop when unset. Masks and crops are not affected.
+    pub ocr_preprocess: Option<OcrPreprocess>,
+    /// Regions whose greyscale pixels have a smaller standard deviation (0-255) are dropped before OCR,
+    /// being flat colour rather than text.
+    pub min_region_contrast: Option<f64>,
+    /// Round the corners of every mask with this radius, at most half the smaller side. Square when unset.
+    pub corner_radius: Option<i32>,
+    /// Fill only the convex hull of the text pixels `mask_text` finds inside each region instead of the whole rectangle.
+    pub hull: bool,
+    /// Rhai source deciding whether a region is masked instead of the targets, see `script::matches`.
+    /// Needs the `script` feature.
+    pub match_script: Option<String>,
+    /// Regions whose text contains any of these are never masked, even when they also contain a target.
+    pub never_mask: Vec<String>,
+}
+
+impl MaskConfig {
+    /// Whether every detected region is masked without looking at its text.
+    pub fn masks_everything(&self) -> bool {
+        self.redact_all || self.detector == Detector::ColorRange
+    }
+
+    /// The mask mode of regions that matched `target`, `mask_mode` unless `target_mask_modes` names one.
+    pub fn mask_mode_for(&self, target: &str) -> MaskMode {
+        self.target_mask_modes.iter().find(|(name, _)| name == target).map(|(_, mode)| *mode).unwrap_or(self.mask_mode)
+    }
+}
+
+impl Default for MaskConfig {
+    fn default() -> Self {
+        MaskConfig {
+            lang: "eng".to_string(),
+            tessdata_dir: None,
+            detector: Detector::Contour,
+            extra_detectors: Vec::new(),
+            force_8bit: false,
+            keep_original: false,
+            include_zones: Vec::new(),
+            crop: None,
+            mask_zone: None,
+            mask_zone_fraction: 0.25,
+            ocr_retries: 1,
+            mask_mode: MaskMode::Bar,
+            target_mask_modes: Vec::new(),
+            contrast_check: None,
+            stamp: None,
+            crops_dir: None,
+            crops_matched_only: false,
+            match_mode: MatchMode::Substring,
+            invert_mask: false,
+            reuse_similar_frames: false,
+            hold_frames: 0,
+            min_height_divisor: DEFAULT_MIN_HEIGHT_DIVISOR,
+            sensitivity: DEFAULT_SENSITIVITY,
+            min_height: None,
+            relax_attempts: 0,
+            max_aspect_ratio: 15.,
+            min_region_area: None,
+            max_region_area: None,
+            hsv_lower: None,
+            hsv_upper: None,
+            hsv_ranges: Vec::new(),
+            close_kernel: None,
+            kernel_shape: MORPH_RECT,
+            contour_approx: CHAIN_APPROX_SIMPLE,
+            fetch_timeout: Duration::from_secs(30),
+            blur_strength: 31,
+            pixel_size: 12,
+            inpaint_method: INPAINT_TELEA,
+            interpolation: None,
+            blur_background: None,
+            require_all: false,
+            tess_vars: Vec::new(),
+            bar_scale: 1.,
+            tiling: None,
+            interactive: false,
+            min_token_length: 0,
+            confusables: Vec::new(),
+            segment_words: false,
+            strip_digits: false,
+            wordlist: HashSet::new(),
+            lang_targets: Vec::new(),
+            mask_scripts: Vec::new(),
+            redact_all: false,
+            auto_orient: false,
+            max_matches: None,
+            mask_qr: false,
+            verify: false,
+            seed: None,
+            ocr_margin: 0,
+            rescan_below: None,
+            ocr_preprocess: None,
+            min_region_contrast: None,
+            corner_radius: None,
+            hull: false,
+            match_script: None,
+            never_mask: Vec::new(),
+        }
+    }
+}
+
+/// Extensions of the files a directory or archive input is searched for.
+pub const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "bmp", "tif", "tiff", "webp", "gif"];
+
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().map(|p| p.starts_with("http://") || p.starts_with("https://")).unwrap_or(false)
+}
+
+#[cfg(feature = "url")]
+fn fetch_bytes(url: &str, timeout: Duration) -> Result<Vec<u8>, MaskMyNameError> {
+    fetch::fetch(url, timeout)
+}
+
+#[cfg(not(feature = "url"))]
+fn fetch_bytes(_url: &str, _timeout: Duration) -> Result<Vec<u8>, MaskMyNameError> {
+    Err(MaskMyNameError::FetchError("this build has no URL support, rebuild with --features url".to_string()))
+}
+
+/// A `.zip` image path is masked entry by entry with `archive::mask_archive` (`--features zip`).
+pub fn is_archive(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.eq_ignore_ascii_case("zip")).unwrap_or(false)
+}
+
+/// `-` as image path reads the encoded image from standard input.
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn decode(bytes: Vec<u8>) -> Result<(Mat, Option<Vec<u8>>), MaskMyNameError> {
+    match imdecode(&VectorOfu8::from_slice(&bytes), IMREAD_UNCHANGED) {
+        Ok(image) if image.rows() > 0 => { Ok((image, Some(bytes))) },
+        _ => { Err(MaskMyNameError::ImageReadError()) }
+    }
+}
+
+fn load_image(image_path: &Path, config: &MaskConfig) -> Result<Mat, MaskMyNameError> {
+    let path = image_path.to_str().ok_or(MaskMyNameError::ImageReadError())?;
+    let (image, bytes) = if is_url(image_path) {
+        decode(fetch_bytes(path, config.fetch_timeout)?)?
+    } else if is_stdin(image_path) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes).map_err(|_| MaskMyNameError::ImageReadError())?;
+        decode(bytes)?
+    } else {
+        match imread(path, IMREAD_UNCHANGED) {
+            Ok(image) => { (image, None) },
+            Err(_) => { return Err(MaskMyNameError::ImageReadError()); }
+        }
+    };
+    if !config.auto_orient {
+        return Ok(image);
+    }
+    let bytes = match bytes {
+        Some(bytes) => { bytes },
+        None => { fs::read(image_path).map_err(|_| MaskMyNameError::ImageReadError())? }
+    };
+    orient(image, &bytes, config)
+}
+
+/// `image` turned upright by the EXIF orientation of its encoded `bytes` with `config.auto_orient`.
+fn orient(image: Mat, bytes: &[u8], config: &MaskConfig) -> Result<Mat, MaskMyNameError> {
+    match orientation::exif_orientation(bytes).filter(|_| config.auto_orient) {
+        Some(value) => { orientation::upright(image, value).map_err(|e| MaskMyNameError::OrientationError(e.message)) },
+        None => { Ok(image) }
+    }
+}
+
+fn depth_max_value(depth: i32) -> f64 {
+    match depth {
+        CV_8U => 255.,
+        CV_8S => 127.,
+        CV_16U => 65535.,
+        CV_16S => 32767.,
+        CV_32F | CV_64F => 1.,
+        _ => 255.,
+    }
+}
+
+/// Detection and Tesseract only understand 8-bit data, so deeper images get a scaled 8-bit copy.
+fn to_8bit(image: &Mat) -> Result<Mat, MaskMyNameError> {
+    if image.depth() == CV_8U {
+        return image.try_clone().map_err(|e| MaskMyNameError::DepthConversionError(e.message));
+    }
+    let mut image_8bit: Mat = Default::default();
+    image.convert_to(&mut image_8bit, CV_8U, 255. / depth_max_value(image.depth()), 0.)
+        .map_err(|e| MaskMyNameError::DepthConversionError(e.message))?;
+    Ok(image_8bit)
+}
+
+/// `MaskConfig::sensitivity` that keeps the detection defaults as they are.
+pub const DEFAULT_SENSITIVITY: u8 = 5;
+pub const MAX_SENSITIVITY: u8 = 10;
+const DEFAULT_MIN_HEIGHT_DIVISOR: i32 = 72;
+
+/// 0.5 at sensitivity 0 to 1.5 at 10, 1.0 by default.
+fn sensitivity_factor(sensitivity: u8) -> f64 {
+    0.5 + sensitivity.min(MAX_SENSITIVITY) as f64 / 10.
+}
+
+/// The default `MaskConfig::min_height_divisor` scaled for `sensitivity`, from 36 at 0 to 108 at 10.
+pub fn sensitivity_min_height_divisor(sensitivity: u8) -> i32 {
+    (DEFAULT_MIN_HEIGHT_DIVISOR as f64 * sensitivity_factor(sensitivity)).round() as i32
+}
+
+/// Dilation passes, one more every other step of sensitivity above the default and one fewer below, at least one.
+fn iterations(frame_height: i32, sensitivity: u8) -> i32 {
+    let base = if frame_height >= 720 {
+        5
+    } else {
+        3
+    };
+    (base + (sensitivity.min(MAX_SENSITIVITY) as i32 - DEFAULT_SENSITIVITY as i32) / 2).max(1)
+}
+
+/// Highest HSV value counted as text without `hsv_upper`, scaled by `sensitivity_factor`.
+fn max_range(frame_height: i32, sensitivity: u8) -> f64 {
+    let base = if frame_height >= 720 {
+        30.
+    } else {
+        80.
+    };
+    base * sensitivity_factor(sensitivity)
+}
+
+/// Pixels inside any of `config.hsv_ranges`, or without those inside `hsv_lower`/`hsv_upper`,
+/// `default_upper` standing in for a missing upper bound. Every extra range is one more full-image pass.
+fn hsv_mask(image_hsv: &Mat, config: &MaskConfig, default_upper: Scalar) -> opencv::Result<Mat> {
+    let ranges = match config.hsv_ranges.is_empty() {
+        true => { vec![(config.hsv_lower.unwrap_or(Scalar::new(0., 0., 0., 0.)), config.hsv_upper.unwrap_or(default_upper))] },
+        false => { config.hsv_ranges.clone() }
+    };
+    let mut union: Mat = Default::default();
+    for (index, (lower, upper)) in ranges.iter().enumerate() {
+        let mut image_mask: Mat = Default::default();
+        in_range(image_hsv, lower, upper, &mut image_mask)?;
+        union = match index {
+            0 => { image_mask },
+            _ => {
+                let mut merged: Mat = Default::default();
+                bitwise_or(&union, &image_mask, &mut merged, &Mat::default())?;
+                merged
+            }
+        };
+    }
+    Ok(union)
+}
+
+/// Takes the image with its alpha channel, if any: fully transparent pixels are never text,
+/// whatever colour they still hold.
+fn mask_text(image: &Mat, config: &MaskConfig) -> Result<Mat, MaskMyNameError> {
+    let image_8bit = to_8bit(image)?;
+    let mut image_hsv: Mat = Default::default();
+    to_bgr(&image_8bit).and_then(|bgr| cvt_color(&bgr, &mut image_hsv, COLOR_BGR2HSV, 0))
+        .map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    let mut image_mask = hsv_mask(&image_hsv, config, Scalar::new(0., 0., max_range(image.rows(), config.sensitivity), 255.))
+        .map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    if image_8bit.channels() == 4 {
+        image_mask = opaque_only(&image_mask, &image_8bit).map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    }
+    let border_value = morphology_default_border_value().map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    if let Some(close_kernel) = config.close_kernel {
+        // fills the gaps inside and between thin or spaced glyphs that dilation alone leaves as separate contours.
+        let kernel = get_structuring_element(config.kernel_shape, close_kernel, Point::new(-1, -1)).map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+        let mut image_closed: Mat = Default::default();
+        morphology_ex(&image_mask, &mut image_closed, MORPH_CLOSE, &kernel, Point::new(-1, -1), 1, BORDER_CONSTANT, border_value)
+            .map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+        image_mask = image_closed;
+    }
+    let kernel = get_structuring_element(config.kernel_shape, Size::new(5, 3), Point::new(-1, -1)).map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    let mut image_dst: Mat = Default::default();
+    dilate(&image_mask, &mut image_dst, &kernel, Point::new(-1, -1), iterations(image.rows(), config.sensitivity), 0, border_value)
+        .map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    Ok(image_dst)
+}
+
+/// `mask` where the alpha channel of the BGRA `image` is above zero.
+fn opaque_only(mask: &Mat, image: &Mat) -> opencv::Result<Mat> {
+    let mut alpha: Mat = Default::default();
+    extract_channel(image, &mut alpha, 3)?;
+    let mut opaque: Mat = Default::default();
+    in_range(&alpha, &Scalar::all(1.), &Scalar::all(255.), &mut opaque)?;
+    let mut masked: Mat = Default::default();
+    bitwise_and(mask, &opaque, &mut masked, &Mat::default())?;
+    Ok(masked)
+}
+
+/// 256-bin histogram of the HSV value channel, the one `mask_text` thresholds on.
+fn value_bins(image: &Mat) -> Result<Vec<f32>, MaskMyNameError> {
+    let image_bgr = to_bgr(&to_8bit(image)?).map_err(|e| MaskMyNameError::HistogramError(e.message))?;
+    let mut image_hsv: Mat = Default::default();
+    cvt_color(&image_bgr, &mut image_hsv, COLOR_BGR2HSV, 0).map_err(|e| MaskMyNameError::HistogramError(e.message))?;
+    let mut images = VectorOfMat::new();
+    images.push(image_hsv);
+    let mut hist: Mat = Default::default();
+    calc_hist(&images, &VectorOfi32::from_slice(&[2]), &Mat::default(), &mut hist,
+              &VectorOfi32::from_slice(&[256]), &VectorOff32::from_slice(&[0., 256.]), false)
+        .map_err(|e| MaskMyNameError::HistogramError(e.message))?;
+    (0..256).map(|i| hist.at::<f32>(i).copied().map_err(|e| MaskMyNameError::HistogramError(e.message))).collect()
+}
+
+/// Value channel histogram of the image at `image_path`, with the highest value `mask_text` counts as text in it.
+pub fn value_histogram(image_path: &Path, config: &MaskConfig) -> Result<(Vec<f32>, f64), MaskMyNameError> {
+    let image = load_image(image_path, config)?;
+    Ok((value_bins(&image)?, max_range(image.rows(), config.sensitivity)))
+}
+
+/// Whether a contour's bounding `rect` in an image of `image_cols` by `image_rows` pixels looks like a line of text:
+/// wider than tall, taller than the minimum height, and neither too long for `config.max_aspect_ratio` nor half the image wide.
+fn keeps_region(rect: &Rect, image_cols: i32, image_rows: i32, config: &MaskConfig) -> bool {
+    let min_height = config.min_height.unwrap_or(image_rows / config.min_height_divisor);
+    rect.height < rect.width && rect.height > min_height
+        && (rect.width as f64 / rect.height as f64) < config.max_aspect_ratio && rect.width < (image_cols / 2)
+        && config.min_region_area.map(|min| rect.area() >= min).unwrap_or(true)
+        && config.max_region_area.map(|max| rect.area() <= max).unwrap_or(true)
+}
+
+fn find_textarea_from_mask(image: &Mat, config: &MaskConfig) -> Result<Vec<Rect>, MaskMyNameError> {
+    let mut contours: VectorOfVectorOfPoint = Default::default();
+    let mut rect_result: Vec<Rect> = Default::default();
+    find_contours(image, &mut contours, RETR_EXTERNAL, config.contour_approx, Default::default()).map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    for contour in contours {
+        let rect = bounding_rect(&contour).map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+        if keeps_region(&rect, image.cols(), image.rows(), config) {
+            rect_result.push(rect);
+        }
+    }
+    Ok(rect_result)
+}
+
+/// Bounding boxes of the connected blobs of pixels inside the HSV bounds or ranges, with no size or shape filtering.
+fn find_color_regions(image: &Mat, config: &MaskConfig) -> Result<Vec<Rect>, MaskMyNameError> {
+    let color_error = |e: opencv::Error| MaskMyNameError::MaskTextError(e.message);
+    let mut image_hsv: Mat = Default::default();
+    cvt_color(image, &mut image_hsv, COLOR_BGR2HSV, 0).map_err(color_error)?;
+    let image_mask = hsv_mask(&image_hsv, config, Scalar::new(180., 255., 255., 255.)).map_err(color_error)?;
+    let mut contours: VectorOfVectorOfPoint = Default::default();
+    find_contours(&image_mask, &mut contours, RETR_EXTERNAL, config.contour_approx, Default::default()).map_err(color_error)?;
+    contours.iter().map(|contour| bounding_rect(&contour).map_err(color_error)).collect()
+}
+
+fn to_bgr(image: &Mat) -> opencv::Result<Mat> {
+    let code = match image.channels() {
+        4 => COLOR_BGRA2BGR,
+        1 => COLOR_GRAY2BGR,
+        _ => { return image.try_clone(); }
+    };
+    let mut image_bgr: Mat = Default::default();
+    cvt_color(image, &mut image_bgr, code, 0)?;
+    Ok(image_bgr)
+}
+
+/// `MSER::create` arguments after the area bounds, OpenCV's defaults: max variation, min diversity, max evolution,
+/// area threshold, min margin and edge blur size. The last four only apply to colour input, which is converted to grey first.
+const MSER_DEFAULTS: (f64, f64, i32, f64, f64, i32) = (0.25, 0.2, 200, 1.01, 0.003, 5);
+/// Grey level step over which a region must stay stable.
+const MSER_DELTA: i32 = 5;
+/// Smallest glyph in pixels. The largest is a quarter of the image, anything bigger is not a glyph.
+const MSER_MIN_AREA: i32 = 30;
+
+/// The bounding boxes of the stable regions of the 8-bit BGR `image`, filled in and dilated like the mask of `mask_text`
+/// so that the glyphs of a line grow into one blob for `find_textarea_from_mask`.
+fn mask_with_mser(image: &Mat, config: &MaskConfig) -> Result<Mat, MaskMyNameError> {
+    let mser_error = |e: opencv::Error| MaskMyNameError::MaskTextError(e.message);
+    let mut gray: Mat = Default::default();
+    cvt_color(image, &mut gray, COLOR_BGR2GRAY, 0).map_err(mser_error)?;
+    let (variation, diversity, evolution, area_threshold, margin, edge_blur) = MSER_DEFAULTS;
+    let max_area = (image.rows() * image.cols() / 4).max(MSER_MIN_AREA + 1);
+    let mut mser = <dyn MSER>::create(MSER_DELTA, MSER_MIN_AREA, max_area, variation, diversity, evolution, area_threshold, margin, edge_blur)
+        .map_err(mser_error)?;
+    let mut regions: VectorOfVectorOfPoint = Default::default();
+    let mut boxes = VectorOfRect::new();
+    mser.detect_regions(&gray, &mut regions, &mut boxes).map_err(mser_error)?;
+    let mut mask = Mat::new_rows_cols_with_default(image.rows(), image.cols(), CV_8UC1, Scalar::all(0.)).map_err(mser_error)?;
+    for area in boxes {
+        rectangle(&mut mask, area, Scalar::all(255.), FILLED, LINE_8, 0).map_err(mser_error)?;
+    }
+    let border_value = morphology_default_border_value().map_err(mser_error)?;
+    let kernel = get_structuring_element(config.kernel_shape, Size::new(5, 3), Point::new(-1, -1)).map_err(mser_error)?;
+    let mut dilated: Mat = Default::default();
+    dilate(&mask, &mut dilated, &kernel, Point::new(-1, -1), iterations(image.rows(), config.sensitivity), 0, border_value).map_err(mser_error)?;
+    Ok(dilated)
+}
+
+/// EAST wants input dimensions that are multiples of 32.
+fn east_input_size(image: &Mat) -> Size {
+    Size::new((image.cols() / 32).max(1) * 32, (image.rows() / 32).max(1) * 32)
+}
+
+fn find_textarea_with_east(image: &Mat, model_path: &Path) -> Result<Vec<Rect>, MaskMyNameError> {
+    let model_path = model_path.to_str().ok_or(MaskMyNameError::DnnDetectionError("model path is not valid UTF-8".to_string()))?;
+    let mut model = TextDetectionModel_EAST::from_file(model_path, "").map_err(|e| MaskMyNameError::DnnDetectionError(e.message))?;
+    model.set_confidence_threshold(0.5).map_err(|e| MaskMyNameError::DnnDetectionError(e.message))?;
+    model.set_nms_threshold(0.4).map_err(|e| MaskMyNameError::DnnDetectionError(e.message))?;
+    model.set_input_params(1.0, east_input_size(image), Scalar::new(123.68, 116.78, 103.94, 0.), true, false)
+        .map_err(|e| MaskMyNameError::DnnDetectionError(e.message))?;
+    let mut detections = VectorOfRotatedRect::new();
+    let mut confidences = VectorOff32::new();
+    let image_bgr = to_bgr(image).map_err(|e| MaskMyNameError::DnnDetectionError(e.message))?;
+    model.detect_text_rectangles(&image_bgr, &mut detections, &mut confidences)
+        .map_err(|e| MaskMyNameError::DnnDetectionError(e.message))?;
+    let mut rect_result: Vec<Rect> = Default::default();
+    for detection in detections {
+        let rect = detection.bounding_rect().map_err(|e| MaskMyNameError::DnnDetectionError(e.message))?;
+        // rotated boxes may reach past the border, and Mat::roi refuses those.
+        let x = rect.x.max(0);
+        let y = rect.y.max(0);
+        let width = (rect.x + rect.width).min(image.cols()) - x;
+        let height = (rect.y + rect.height).min(image.rows()) - y;
+        if width > 0 && height > 0 {
+            rect_result.push(Rect::new(x, y, width, height));
+        }
+    }
+    Ok(rect_result)
+}
+
+fn intersects(a: &Rect, b: &Rect) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+}
+
+pub fn format_rect(rect: &Rect) -> String {
+    format!("{},{},{},{}", rect.x, rect.y, rect.width, rect.height)
+}
+
+fn check_zones(image: &Mat, zones: &[Rect]) -> Result<(), MaskMyNameError> {
+    match zones.iter().find(|z| z.x < 0 || z.y < 0 || z.x + z.width > image.cols() || z.y + z.height > image.rows()) {
+        Some(zone) => { Err(MaskMyNameError::RegionOutOfBounds(format_rect(zone))) },
+        None => { Ok(()) }
+    }
+}
+
+fn run_detector(detector: &Detector, image: &Mat, config: &MaskConfig, timings: &mut Timings) -> Result<Vec<Rect>, MaskMyNameError> {
+    let started = Instant::now();
+    let mut mask_time = Duration::ZERO;
+    let areas = match detector {
+        Detector::Contour => {
+            let mask = mask_text(image, config)?;
+            mask_time = started.elapsed();
+            find_textarea_from_mask(&mask, config)
+        },
+        Detector::Dnn(model_path) => { find_textarea_with_east(&detection_input(image)?, model_path) },
+        Detector::Mser => {
+            let mask = mask_with_mser(&detection_input(image)?, config)?;
+            mask_time = started.elapsed();
+            find_textarea_from_mask(&mask, config)
+        },
+        Detector::ColorRange => { find_color_regions(&detection_input(image)?, config) },
+        Detector::Boxes(boxes) => { check_zones(image, boxes).map(|_| boxes.clone()) }
+    };
+    timings.mask_text += mask_time;
+    timings.find_regions += started.elapsed() - mask_time;
+    areas
+}
+
+/// Boxes overlapping an already kept one by more than this intersection over union are dropped when merging detectors.
+const MERGE_IOU: f64 = 0.5;
+
+fn iou(a: &Rect, b: &Rect) -> f64 {
+    let width = (a.x + a.width).min(b.x + b.width) - a.x.max(b.x);
+    let height = (a.y + a.height).min(b.y + b.height) - a.y.max(b.y);
+    if width <= 0 || height <= 0 {
+        return 0.;
+    }
+    let intersection = (width * height) as f64;
+    intersection / (a.area() as f64 + b.area() as f64 - intersection)
+}
+
+/// Regions of `config.detector`, united with those of every `config.extra_detectors` in order. A box is kept unless it
+/// overlaps an earlier kept one by more than `MERGE_IOU`, so earlier detectors win. How many kept boxes each detector
+/// contributed is added to `contributions`.
+fn detect_regions(image: &Mat, config: &MaskConfig, timings: &mut Timings,
+                  contributions: &mut Vec<(String, usize)>) -> Result<Vec<Rect>, MaskMyNameError> {
+    if config.extra_detectors.is_empty() {
+        return run_detector(&config.detector, image, config, timings);
+    }
+    let mut kept: Vec<Rect> = Vec::new();
+    for detector in std::iter::once(&config.detector).chain(&config.extra_detectors) {
+        let before = kept.len();
+        for area in run_detector(detector, image, config, timings)? {
+            if kept.iter().all(|k| iou(k, &area) <= MERGE_IOU) {
+                kept.push(area);
+            }
+        }
+        match contributions.iter_mut().find(|(name, _)| name == detector.name()) {
+            Some((_, count)) => { *count += kept.len() - before; },
+            None => { contributions.push((detector.name().to_string(), kept.len() - before)); }
+        }
+    }
+    Ok(kept)
+}
+
+/// `zone` of a `cols` x `rows` image, `fraction` of it along the zone's axis.
+fn zone_rect(zone: MaskZone, fraction: f64, cols: i32, rows: i32) -> Rect {
+    let (width, height) = ((cols as f64 * fraction).round() as i32, (rows as f64 * fraction).round() as i32);
+    match zone {
+        MaskZone::Top => { Rect::new(0, 0, cols, height) },
+        MaskZone::Bottom => { Rect::new(0, rows - height, cols, height) },
+        MaskZone::Left => { Rect::new(0, 0, width, rows) },
+        MaskZone::Right => { Rect::new(cols - width, 0, width, rows) },
+        MaskZone::Center => { Rect::new((cols - width) / 2, (rows - height) / 2, width, height) }
+    }
+}
+
+/// Whether the centre of `area` falls inside `band`, so a region straddling its edge counts for the side most of it is on.
+fn in_zone(area: &Rect, band: &Rect) -> bool {
+    let (center_x, center_y) = (area.x + area.width / 2, area.y + area.height / 2);
+    (band.x..band.x + band.width).contains(&center_x) && (band.y..band.y + band.height).contains(&center_y)
+}
+
+fn filter_zones(image: &Mat, areas: Vec<Rect>, config: &MaskConfig) -> Result<Vec<Rect>, MaskMyNameError> {
+    let areas = match config.mask_zone {
+        Some(zone) => {
+            let band = zone_rect(zone, config.mask_zone_fraction, image.cols(), image.rows());
+            areas.into_iter().filter(|area| in_zone(area, &band)).collect()
+        },
+        None => { areas }
+    };
+    if config.include_zones.is_empty() {
+        return Ok(areas);
+    }
+    check_zones(image, &config.include_zones)?;
+    Ok(areas.into_iter().filter(|area| config.include_zones.iter().any(|zone| intersects(area, zone))).collect())
+}
+
+fn find_textarea(image: &Mat, config: &MaskConfig, timings: &mut Timings,
+                 contributions: &mut Vec<(String, usize)>) -> Result<Vec<Rect>, MaskMyNameError> {
+    let areas = detect_regions(image, config, timings, contributions)?;
+    filter_zones(image, areas, config)
+}
+
+/// HSV conversion, the EAST model and Tesseract all want plain 8-bit BGR, whatever the input is.
+fn detection_input(image: &Mat) -> Result<Mat, MaskMyNameError> {
+    to_bgr(&to_8bit(image)?).map_err(|e| MaskMyNameError::MaskTextError(e.message))
+}
+
+/// Start and length of each tile along one axis, consecutive tiles sharing `overlap` pixels,
+/// and the span of the axis each tile is responsible for. The spans split every overlap in half,
+/// so they cover the axis once without gaps.
+fn tile_spans(length: i32, tiling: &Tiling) -> Vec<(i32, i32, i32, i32)> {
+    let step = tiling.size - tiling.overlap;
+    let mut spans = Vec::new();
+    let mut start = 0;
+    loop {
+        let size = tiling.size.min(length - start);
+        let last = start + size >= length;
+        let own_start = if start == 0 { 0 } else { start + tiling.overlap / 2 };
+        let own_end = if last { length } else { start + size - (tiling.overlap - tiling.overlap / 2) };
+        spans.push((start, size, own_start, own_end));
+        if last {
+            return spans;
+        }
+        start += step;
+    }
+}
+
+/// Bounding boxes of every QR code in `image`, clipped to it, with their decoded text.
+fn find_codes(image: &Mat) -> opencv::Result<Vec<CodeMatch>> {
+    let detector = QRCodeDetector::default()?;
+    let mut payloads = VectorOfString::new();
+    let mut points: Mat = Default::default();
+    let mut straight: Mat = Default::default();
+    if !detector.detect_and_decode_multi(image, &mut payloads, &mut points, &mut straight)? {
+        return Ok(Vec::new());
+    }
+    let mut codes = Vec::new();
+    // one row of four corners per code.
+    for (row, payload) in (0..points.rows()).zip(payloads.iter()) {
+        let corners: Vec<Point2f> = (0..4).map(|col| points.at_2d::<Point2f>(row, col).copied()).collect::<opencv::Result<_>>()?;
+        let left = corners.iter().map(|p| p.x).fold(f32::MAX, f32::min).floor().max(0.) as i32;
+        let top = corners.iter().map(|p| p.y).fold(f32::MAX, f32::min).floor().max(0.) as i32;
+        let right = (corners.iter().map(|p| p.x).fold(f32::MIN, f32::max).ceil() as i32).min(image.cols());
+        let bottom = (corners.iter().map(|p| p.y).fold(f32::MIN, f32::max).ceil() as i32).min(image.rows());
+        if right > left && bottom > top {
+            codes.push(CodeMatch { area: Rect::new(left, top, right - left, bottom - top), payload });
+        }
+    }
+    Ok(codes)
+}
+
+/// Detection tile by tile so only one tile at a time is converted for detection.
+/// A region in the overlap between tiles is found by both and kept by the tile whose span holds its centre,
+/// so a line cut by one tile's edge is still found whole by its neighbour when it fits in the overlap.
+fn find_textarea_tiled(image: &Mat, tiling: &Tiling, config: &MaskConfig, timings: &mut Timings,
+                       contributions: &mut Vec<(String, usize)>) -> Result<Vec<Rect>, MaskMyNameError> {
+    let mut areas = Vec::new();
+    for (y, height, own_top, own_bottom) in tile_spans(image.rows(), tiling) {
+        for (x, width, own_left, own_right) in tile_spans(image.cols(), tiling) {
+            let tile = Mat::roi(image, Rect::new(x, y, width, height)).map_err(|e| MaskMyNameError::RoiCreationError(e.message))?;
+            for area in detect_regions(&tile, config, timings, contributions)? {
+                let area = Rect::new(area.x + x, area.y + y, area.width, area.height);
+                let (center_x, center_y) = (area.x + area.width / 2, area.y + area.height / 2);
+                if (own_left..own_right).contains(&center_x) && (own_top..own_bottom).contains(&center_y) {
+                    areas.push(area);
+                }
+            }
+        }
+    }
+    filter_zones(image, areas, config)
+}
+
+fn scan_image(tess: &mut TessBaseApi, image: &Mat) -> Result<Text, MaskMyNameError> {
+    // set_image takes one byte per channel and a tightly packed stride.
+    if image.depth() != CV_8U || !image.is_continuous() {
+        return Err(MaskMyNameError::TessSetImageError());
+    }
+    tess.set_image(image.data_bytes().map_err(|_| MaskMyNameError::TessSetImageError())?,
+                   image.cols() as c_int,
+                   image.rows() as c_int,
+                   image.channels(), (image.cols() * image.channels()) as c_int).map_err(|_| MaskMyNameError::TessSetImageError())?;
+    match tess.get_utf8_text() {
+        Ok(text) => { Ok(text) },
+        Err(_) => { Err(MaskMyNameError::TessGetTextError()) }
+    }
+}
+
+/// Only get_utf8_text failures are retried, set_image errors come from the Mat itself and would fail again.
+/// Every retry is counted in `pass.retries`.
+fn scan_image_with_retry(pass: &mut OcrPass, image: &Mat, retries: u32) -> Result<Text, MaskMyNameError> {
+    let mut attempt = 0;
+    loop {
+        match scan_image(&mut pass.tess, image) {
+            Err(MaskMyNameError::TessGetTextError()) if attempt < retries => {
+                attempt += 1;
+                pass.retries += 1;
+            },
+            result => { return result; }
+        }
+    }
+}
+
+fn masking_bar(roi: &Mat, color: Scalar) -> Result<Mat, MaskMyNameError> {
+    match Mat::new_rows_cols_with_default(roi.rows(), roi.cols(), roi.typ(), color) {
+        Ok(mat) => { Ok(mat) }
+        Err(e) => { Err(MaskMyNameError::MaskingBarCreationError(e.message)) }
+    }
+}
+
+/// Mean colour of a `width` pixel ring around `area`, clipped to the image.
+/// `None` when the region leaves no ring at all, e.g. it covers the whole image.
+fn ring_color(image: &Mat, area: Rect, width: i32) -> opencv::Result<Option<Scalar>> {
+    let x = (area.x - width).max(0);
+    let y = (area.y - width).max(0);
+    let outer = Rect::new(x, y,
+                          (area.x + area.width + width).min(image.cols()) - x,
+                          (area.y + area.height + width).min(image.rows()) - y);
+    if outer.width == area.width && outer.height == area.height {
+        return Ok(None);
+    }
+    let ring = Mat::new_rows_cols_with_default(outer.height, outer.width, CV_8UC1, Scalar::all(255.))?;
+    let mut inner = Mat::roi(&ring, Rect::new(area.x - outer.x, area.y - outer.y, area.width, area.height))?;
+    Mat::new_rows_cols_with_default(area.height, area.width, CV_8UC1, Scalar::all(0.))?.copy_to(&mut inner)?;
+    Ok(Some(mean(&Mat::roi(image, outer)?, &ring)?))
+}
+
+/// Width of the ring around a region that stands for its background.
+const RING_WIDTH: i32 = 4;
+
+fn mask_color(image: &Mat, area: Rect, mode: MaskMode) -> Result<Scalar, MaskMyNameError> {
+    let bar = Scalar::all(depth_max_value(image.depth()));
+    match mode {
+        MaskMode::Bar | MaskMode::Blur | MaskMode::Pixelate | MaskMode::Strong | MaskMode::Noise | MaskMode::Inpaint => { Ok(bar) },
+        MaskMode::AutoColor => {
+            ring_color(image, area, RING_WIDTH).map(|color| color.unwrap_or(bar))
+                .map_err(|e| MaskMyNameError::MaskingBarCreationError(e.message))
+        }
+    }
+}
+
+/// Contrast ratio below which a bar counts as blending into its background, the WCAG minimum for graphics.
+pub const MIN_CONTRAST: f64 = 3.;
+
+/// WCAG relative luminance, 0 to 1, of a BGR or grey `color` in a depth whose full scale is `max`.
+fn relative_luminance(color: Scalar, channels: i32, max: f64) -> f64 {
+    let linear = |c: f64| {
+        let c = (c / max).clamp(0., 1.);
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    match channels {
+        1 | 2 => { linear(color[0]) },
+        _ => { 0.0722 * linear(color[0]) + 0.7152 * linear(color[1]) + 0.2126 * linear(color[2]) }
+    }
+}
+
+/// WCAG contrast ratio of two colours of `image`, from 1 (the same) to 21 (black on white).
+fn contrast_ratio(image: &Mat, a: Scalar, b: Scalar) -> f64 {
+    let max = depth_max_value(image.depth());
+    let (a, b) = (relative_luminance(a, image.channels(), max), relative_luminance(b, image.channels(), max));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// The bar colour for `area` after `config.contrast_check`, and whether it was below `MIN_CONTRAST`.
+fn contrasting_color(image: &Mat, area: Rect, color: Scalar, mode: MaskMode, config: &MaskConfig) -> Result<(Scalar, bool), MaskMyNameError> {
+    let check = match (config.contrast_check, mode) {
+        (Some(check), MaskMode::Bar) => { check },
+        _ => { return Ok((color, false)); }
+    };
+    let background = match ring_color(image, area, RING_WIDTH).map_err(|e| MaskMyNameError::MaskingBarCreationError(e.message))? {
+        Some(background) => { background },
+        None => { return Ok((color, false)); }
+    };
+    if contrast_ratio(image, color, background) >= MIN_CONTRAST {
+        return Ok((color, false));
+    }
+    let (black, white) = (Scalar::new(0., 0., 0., color[3]), Scalar::all(depth_max_value(image.depth())));
+    Ok(match check {
+        ContrastCheck::Warn => { (color, true) },
+        ContrastCheck::Switch if contrast_ratio(image, black, background) >= contrast_ratio(image, white, background) => { (black, true) },
+        ContrastCheck::Switch => { (white, true) }
+    })
+}
+
+fn tessdata_path(tessdata_dir: Option<&Path>) -> Result<Option<CString>, MaskMyNameError> {
+    tessdata_dir.map(|dir| dir.to_str()
+        .and_then(|dir| CString::new(dir).ok())
+        .ok_or(MaskMyNameError::InvalidArgument(format!("invalid tessdata directory {}", dir.display()))))
+        .transpose()
+}
+
+/// `config.tess_vars` are applied to every new instance, so each image of a batch starts from the same settings.
+fn init_tess(config: &MaskConfig, lang: &str) -> Result<TessBaseApi, MaskMyNameError> {
+    let lang_c = CString::new(lang).map_err(|_| MaskMyNameError::TessInitError(lang.to_string()))?;
+    let datapath = tessdata_path(config.tessdata_dir.as_deref())?;
+    let mut ocr = TessBaseApi::create();
+    if ocr.init_2(datapath.as_deref(), Some(lang_c.as_c_str())).is_err() {
+        return Err(MaskMyNameError::TessInitError(lang.to_string()));
+    }
+    for (name, value) in &config.tess_vars {
+        let rejected = || MaskMyNameError::TessVariableError(format!("{}={}", name, value));
+        let name_c = CString::new(name.as_str()).map_err(|_| rejected())?;
+        let value_c = CString::new(value.as_str()).map_err(|_| rejected())?;
+        ocr.set_variable(&name_c, &value_c).map_err(|_| rejected())?;
+    }
+    Ok(ocr)
+}
+
+/// The plumbing crate has no wrapper for GetAvailableLanguagesAsVector, so this talks to tesseract-sys directly.
+pub fn available_languages(tessdata_dir: Option<&Path>) -> Result<Vec<String>, MaskMyNameError> {
+    let datapath = tessdata_path(tessdata_dir)?;
+    let mut languages = Vec::new();
+    unsafe {
+        let handle = TessBaseAPICreate();
+        if TessBaseAPIInit3(handle, datapath.as_deref().map(CStr::as_ptr).unwrap_or(std::ptr::null()), std::ptr::null()) != 0 {
+            TessBaseAPIDelete(handle);
+            let location = tessdata_dir.map(|dir| dir.display().to_string()).unwrap_or("in the default location or $TESSDATA_PREFIX".to_string());
+            return Err(MaskMyNameError::TessdataError(location));
+        }
+        let list = TessBaseAPIGetAvailableLanguagesAsVector(handle);
+        if !list.is_null() {
+            let mut entry = list;
+            while !(*entry).is_null() {
+                languages.push(CStr::from_ptr(*entry).to_string_lossy().into_owned());
+                entry = entry.add(1);
+            }
+            TessDeleteTextArray(list);
+        }
+        TessBaseAPIDelete(handle);
+    }
+    languages.sort();
+    Ok(languages)
+}
+
+fn supplement_target_string(target: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    match target.contains("_") {
+        true => {
+            strings.push(target.to_lowercase());
+            strings.push(target.replace("_", " ").to_lowercase());
+        }
+        false => {
+            strings.push(target.to_lowercase());
+        }
+    }
+    strings
+}
+
+fn normalize_text(text: &str) -> String {
+    text.to_lowercase().replace(".", "").replace(",", "")
+}
+
+/// `text` without numeric characters. A space left doubled by a dropped number is dropped too,
+/// so `jane 12 doe` becomes `jane doe`.
+fn strip_digits(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut dropped = false;
+    for c in text.chars() {
+        if c.is_numeric() {
+            dropped = true;
+            continue;
+        }
+        if c == ' ' && dropped && stripped.ends_with(' ') {
+            continue;
+        }
+        stripped.push(c);
+        dropped = false;
+    }
+    stripped
+}
+
+/// Characters OCR commonly mistakes for each other. Each group collapses to its first character.
+pub const DEFAULT_CONFUSABLES: [&str; 4] = ["0o", "1il|", "5s", "8b"];
+
+/// Replaces every character of a confusable group with the group's first one,
+/// so text and targets agree whichever of them OCR picked.
+fn canonicalize(text: &str, groups: &[String]) -> String {
+    text.chars()
+        .map(|c| groups.iter().find(|g| g.contains(c)).and_then(|g| g.chars().next()).unwrap_or(c))
+        .collect()
+}
+
+fn digits(text: &str) -> String {
+    text.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            current[j + 1] = (previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Similarity of `target` to its best matching window in `text`, so a name inside a longer line can still score 1.0.
+fn similarity(text: &str, target: &str) -> f64 {
+    let text: Vec<char> = text.chars().collect();
+    let target: Vec<char> = target.chars().collect();
+    if target.is_empty() {
+        return 0.;
+    }
+    let mut best = 0.;
+    for len in target.len().saturating_sub(1)..=(target.len() + 1) {
+        if len == 0 || len > text.len() {
+            continue;
+        }
+        for window in text.windows(len) {
+            let score = 1. - levenshtein(window, &target) as f64 / len.max(target.len()) as f64;
+            if score > best {
+                best = score;
+            }
+        }
+    }
+    best
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GlobToken {
+    Literal(char),
+    /// `*`
+    AnyRun,
+    /// `?`
+    AnyChar,
+}
+
+/// `*` and `?` as wildcards, `\` making the next character literal, e.g. `\*`. A trailing `\` is literal itself.
+fn glob_tokens(pattern: &str) -> Vec<GlobToken> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '\\' => { GlobToken::Literal(chars.next().unwrap_or('\\')) },
+            '*' => { GlobToken::AnyRun },
+            '?' => { GlobToken::AnyChar },
+            c => { GlobToken::Literal(c) }
+        });
+    }
+    tokens
+}
+
+/// Whether `pattern` matches some part of `text`, `*` standing for any run of characters, none included,
+/// and `?` for exactly one. Backtracks to the last `*` on a mismatch, so it is linear in practice.
+fn glob_matches(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    // a match anywhere is a match of the whole text with a `*` on either side.
+    let tokens: Vec<GlobToken> = std::iter::once(GlobToken::AnyRun).chain(glob_tokens(pattern)).chain([GlobToken::AnyRun]).collect();
+    let (mut t, mut p) = (0, 0);
+    // the last `*` and where in the text it currently ends.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(GlobToken::AnyRun) => {
+                star = Some((p, t));
+                p += 1;
+            },
+            Some(GlobToken::AnyChar) => {
+                t += 1;
+                p += 1;
+            },
+            Some(GlobToken::Literal(c)) if *c == text[t] => {
+                t += 1;
+                p += 1;
+            },
+            _ => {
+                let Some((star_p, star_t)) = star else {
+                    return false;
+                };
+                p = star_p + 1;
+                t = star_t + 1;
+                star = Some((star_p, star_t + 1));
+            }
+        }
+    }
+    tokens[p..].iter().all(|token| *token == GlobToken::AnyRun)
+}
+
+fn variant_matches(picked: &str, variant: &str, config: &MaskConfig) -> bool {
+    match config.match_mode {
+        MatchMode::Substring | MatchMode::Fuzzy(_) => { picked.contains(variant) },
+        // Tesseract ends its output with a newline, which is not part of the label.
+        MatchMode::Exact => { picked.trim() == variant },
+        // an empty variant would be a prefix and a suffix of everything.
+        MatchMode::Prefix => { !variant.is_empty() && picked.trim().starts_with(variant) },
+        MatchMode::Suffix => { !variant.is_empty() && picked.trim().ends_with(variant) },
+        MatchMode::Numeric => {
+            // a target without digits would reduce to "" and match everything.
+            let variant = digits(variant);
+            !variant.is_empty() && digits(picked).contains(&variant)
+        },
+        MatchMode::Phonetic => { phonetic::sounds_like(picked, variant) },
+        MatchMode::Glob => { glob_tokens(variant).iter().any(|token| matches!(token, GlobToken::Literal(_))) && glob_matches(picked, variant) }
+    }
+}
+
+/// Index and score of the target that wins this text, if any does.
+/// Fuzzy mode looks at every target and keeps the best score; earlier targets win ties.
+/// Target variants come in already canonicalized by `build_targets`.
+fn best_match(picked: &str, targets: &[Target], config: &MaskConfig) -> Option<(usize, f64)> {
+    let canonical;
+    let picked = match config.confusables.is_empty() {
+        true => { picked },
+        false => {
+            canonical = canonicalize(picked, &config.confusables);
+            canonical.as_str()
+        }
+    };
+    match config.match_mode {
+        MatchMode::Fuzzy(threshold) => {
+            let mut best: Option<(usize, f64)> = None;
+            for (index, target) in targets.iter().enumerate() {
+                let score = target.variants.iter().map(|v| similarity(picked, v)).fold(0., f64::max);
+                if best.map(|(_, s)| score > s).unwrap_or(true) {
+                    best = Some((index, score));
+                }
+            }
+            best.filter(|(_, score)| *score >= threshold)
+        },
+        _ => {
+            targets.iter().position(|t| t.variants.iter().any(|v| variant_matches(picked, v, config)))
+                .map(|index| (index, 1.))
+        }
+    }
+}
+
+fn stem_of(path: &Path) -> &str {
+    path.file_stem().unwrap_or("output".as_ref()).to_str().unwrap_or("output")
+}
+
+fn write_crop(crops_dir: &Path, label: &str, index: usize, area: Rect, roi: &Mat) -> Result<(), MaskMyNameError> {
+    fs::create_dir_all(crops_dir).map_err(|_| MaskMyNameError::ImageWriteError())?;
+    let name = format!("{}_{}_{}_{}_{}x{}.png", label, index, area.x, area.y, area.width, area.height);
+    let crop_path = crops_dir.join(name);
+    match imwrite(crop_path.to_str().ok_or(MaskMyNameError::ImageWriteError())?, roi, &Default::default()) {
+        Ok(true) => { Ok(()) },
+        _ => { Err(MaskMyNameError::ImageWriteError()) }
+    }
+}
+
+/// `MissingTargetsError` naming every target of every pass that is not among `matched`.
+fn check_all_matched<'a>(passes: &[OcrPass], matched: impl Iterator<Item = &'a str>) -> Result<(), MaskMyNameError> {
+    let matched: Vec<&str> = matched.collect();
+    let missing: Vec<&str> = passes.iter().flat_map(|pass| pass.targets.iter())
+        .map(|t| t.name.as_str()).filter(|t| !matched.contains(t)).collect();
+    match missing.is_empty() {
+        true => { Ok(()) },
+        false => { Err(MaskMyNameError::MissingTargetsError(missing.join(", "))) }
+    }
+}
+
+/// Variants shorter than `config.min_token_length` characters are dropped, so a target made only of
+/// short words never matches.
+fn build_targets(target_strings: &[String], config: &MaskConfig) -> Vec<Target> {
+    target_strings.iter()
+        .map(|t| Target {
+            name: t.clone(),
+            variants: supplement_target_string(t).into_iter()
+                .filter(|v| v.chars().count() >= config.min_token_length)
+                .map(|v| canonicalize(&v, &config.confusables))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Reported as the target of every region masked by `redact_all`.
+pub const REDACT_ALL_TARGET: &str = "*";
+
+/// Reported as the target of every region a match script asked to mask.
+pub const SCRIPT_TARGET: &str = "script";
+
+/// Followed by the Unicode script name, e.g. `script:Han`, reported as the target of regions masked for `mask_scripts`.
+pub const WRITING_SCRIPT_TARGET_PREFIX: &str = "script:";
+
+/// A Tesseract instance and the targets matched against what it reads.
+struct OcrPass {
+    tess: TessBaseApi,
+    targets: Vec<Target>,
+    /// Scans repeated for `MaskConfig::rescan_below` so far, over every image the pass read.
+    rescans: usize,
+    /// Scans repeated for `MaskConfig::ocr_retries` so far, counted the same way.
+    retries: usize,
+}
+
+/// One pass in `config.lang` for the plain targets, plus one per language of `config.lang_targets`.
+/// An empty target is contained in every text and would mask every region, so it is refused;
+/// `config.masks_everything()` is the explicit way to ask for that.
+fn build_passes(target_strings: &[String], config: &MaskConfig) -> Result<Vec<OcrPass>, MaskMyNameError> {
+    let names = target_strings.iter().chain(config.lang_targets.iter().map(|(_, name)| name));
+    if !config.masks_everything() && names.clone().any(|name| name.trim().is_empty()) {
+        return Err(MaskMyNameError::EmptyTarget());
+    }
+    let mut passes = Vec::new();
+    if !target_strings.is_empty() {
+        passes.push(OcrPass { tess: init_tess(config, &config.lang)?, targets: build_targets(target_strings, config), rescans: 0, retries: 0 });
+    }
+    let mut langs: Vec<&str> = Vec::new();
+    for (lang, _) in &config.lang_targets {
+        if !langs.contains(&lang.as_str()) {
+            langs.push(lang);
+        }
+    }
+    for lang in langs {
+        let names: Vec<String> = config.lang_targets.iter().filter(|(l, _)| l == lang).map(|(_, name)| name.clone()).collect();
+        passes.push(OcrPass { tess: init_tess(config, lang)?, targets: build_targets(&names, config), rescans: 0, retries: 0 });
+    }
+    // a match script needs no targets, but still something to read the text.
+    if passes.is_empty() && config.match_script.is_some() {
+        passes.push(OcrPass { tess: init_tess(config, &config.lang)?, targets: Vec::new(), rescans: 0, retries: 0 });
+    }
+    Ok(passes)
+}
+
+/// Upscaling factor of the crops scanned again for `MaskConfig::rescan_below`.
+const RESCAN_SCALE: f64 = 2.;
+
+/// Standard deviation of the greyscale pixels of `image`, an 8-bit BGR crop, 0 for a flat colour.
+fn region_contrast(image: &Mat) -> opencv::Result<f64> {
+    let mut gray: Mat = Default::default();
+    cvt_color(image, &mut gray, COLOR_BGR2GRAY, 0)?;
+    let (mut mean, mut stddev): (Mat, Mat) = Default::default();
+    mean_std_dev(&gray, &mut mean, &mut stddev, &Mat::default())?;
+    stddev.at::<f64>(0).copied()
+}
+
+/// `image`, an 8-bit BGR crop, after `preprocess`.
+fn preprocess_crop(image: &Mat, preprocess: OcrPreprocess) -> opencv::Result<Mat> {
+    let mut gray: Mat = Default::default();
+    cvt_color(image, &mut gray, COLOR_BGR2GRAY, 0)?;
+    let mut processed: Mat = Default::default();
+    match preprocess {
+        OcrPreprocess::Grayscale => { return Ok(gray); },
+        OcrPreprocess::Otsu => { threshold(&gray, &mut processed, 0., 255., THRESH_BINARY | THRESH_OTSU)?; },
+        OcrPreprocess::Stretch => { normalize(&gray, &mut processed, 0., 255., NORM_MINMAX, -1, &Mat::default())?; }
+    }
+    Ok(processed)
+}
+
+fn read_region(pass: &mut OcrPass, image: &Mat, config: &MaskConfig) -> Result<String, MaskMyNameError> {
+    let preprocessed;
+    let image = match config.ocr_preprocess {
+        Some(preprocess) => {
+            preprocessed = preprocess_crop(image, preprocess).map_err(|e| MaskMyNameError::OcrPreprocessError(e.message))?;
+            &preprocessed
+        },
+        None => { image }
+    };
+    let mut text = scan_image_with_retry(pass, image, config.ocr_retries)?;
+    if let Some(threshold) = config.rescan_below {
+        if pass.tess.mean_text_conf() < threshold {
+            let mut upscaled: Mat = Default::default();
+            resize(image, &mut upscaled, Size::new(0, 0), RESCAN_SCALE, RESCAN_SCALE, config.interpolation.unwrap_or(INTER_CUBIC))
+                .map_err(|e| MaskMyNameError::RescanError(e.message))?;
+            text = scan_image_with_retry(pass, &upscaled, config.ocr_retries)?;
+            pass.rescans += 1;
+        }
+    }
+    let raw = text.as_ref().to_str().unwrap_or("");
+    let picked = match config.segment_words {
+        true => { normalize_text(&segment::segment(raw, &config.wordlist)) },
+        false => { normalize_text(raw) }
+    };
+    Ok(match config.strip_digits {
+        true => { strip_digits(&picked) },
+        false => { picked }
+    })
+}
+
+/// OCRs every masked area of the already masked `image` again and fails with the ones where a target still reads through.
+fn verify_masks(passes: &mut [OcrPass], image: &Mat, masked: &[Rect], config: &MaskConfig) -> Result<(), MaskMyNameError> {
+    let mut readable = Vec::new();
+    for area in masked {
+        let roi = Mat::roi(image, *area).map_err(|e| MaskMyNameError::RoiCreationError(e.message))
+            .and_then(|roi| detection_input(&roi))?;
+        for pass in passes.iter_mut() {
+            let picked = read_region(pass, &roi, config)?;
+            if best_match(&picked, &pass.targets, config).is_some() {
+                readable.push(format_rect(area));
+                break;
+            }
+        }
+    }
+    match readable.is_empty() {
+        true => { Ok(()) },
+        false => { Err(MaskMyNameError::VerificationError(readable.join(" "))) }
+    }
+}
+
+/// What OCR made of a detected region.
+enum Verdict {
+    /// OCR text, target name and score.
+    Matched(String, String, f64),
+    /// What the first pass read.
+    Unmatched(String),
+    /// The text, which contains an entry of `config.never_mask`.
+    Denied(String),
+}
+
+/// Whether the normalized `picked` contains any of the `config.never_mask` entries, compared the way targets are.
+fn is_denied(picked: &str, config: &MaskConfig) -> bool {
+    let picked = canonicalize(picked, &config.confusables);
+    config.never_mask.iter()
+        .map(|entry| canonicalize(&normalize_text(entry), &config.confusables))
+        .any(|entry| !entry.trim().is_empty() && picked.contains(entry.trim()))
+}
+
+#[cfg(feature = "script")]
+fn run_match_script(source: &str, text: &str, area: Rect, confidence: i32, targets: &[Target]) -> Result<bool, MaskMyNameError> {
+    let names: Vec<String> = targets.iter().map(|t| t.name.clone()).collect();
+    script::matches(source, text, area, confidence, &names)
+}
+
+#[cfg(not(feature = "script"))]
+fn run_match_script(_source: &str, _text: &str, _area: Rect, _confidence: i32, _targets: &[Target]) -> Result<bool, MaskMyNameError> {
+    Err(MaskMyNameError::ScriptError("this build has no scripting support, rebuild with --features script".to_string()))
+}
+
+/// Best match over all passes. Outside fuzzy mode the first pass with a match wins and the later ones are not run.
+/// A denylisted text in any pass that was run overrides the match. With `config.match_script` the script decides
+/// instead of the targets, and the first pass it accepts wins.
+/// With `config.masks_everything()` every region matches without being OCR'd, so the denylist is not checked.
+fn match_region(passes: &mut [OcrPass], image: &Mat, area: Rect, config: &MaskConfig) -> Result<Verdict, MaskMyNameError> {
+    if config.masks_everything() {
+        return Ok(Verdict::Matched(String::new(), REDACT_ALL_TARGET.to_string(), 1.));
+    }
+    let mut best: Option<(String, String, f64)> = None;
+    let mut first_read: Option<String> = None;
+    let mut in_script: Option<(String, Script)> = None;
+    for pass in passes.iter_mut() {
+        let picked = read_region(pass, image, config)?;
+        if is_denied(&picked, config) {
+            return Ok(Verdict::Denied(picked.trim().to_string()));
+        }
+        first_read.get_or_insert_with(|| picked.trim().to_string());
+        if in_script.is_none() {
+            in_script = picked.chars().map(|c| c.script()).find(|script| config.mask_scripts.contains(script))
+                .map(|script| (picked.trim().to_string(), script));
+        }
+        if let Some(source) = &config.match_script {
+            let confidence = pass.tess.mean_text_conf();
+            if run_match_script(source, picked.trim(), area, confidence, &pass.targets)? {
+                return Ok(Verdict::Matched(picked.trim().to_string(), SCRIPT_TARGET.to_string(), confidence as f64 / 100.));
+            }
+            continue;
+        }
+        if let Some((target_index, score)) = best_match(&picked, &pass.targets, config) {
+            if best.as_ref().map(|(_, _, s)| score > *s).unwrap_or(true) {
+                best = Some((picked.trim().to_string(), pass.targets[target_index].name.clone(), score));
+            }
+            if !matches!(config.match_mode, MatchMode::Fuzzy(_)) {
+                break;
+            }
+        }
+    }
+    // a target match is the more specific report.
+    Ok(match (best, in_script) {
+        (Some((text, target, score)), _) => { Verdict::Matched(text, target, score) },
+        (None, Some((text, script))) => { Verdict::Matched(text, format!("{}{}", WRITING_SCRIPT_TARGET_PREFIX, script.full_name()), 1.) },
+        (None, None) => { Verdict::Unmatched(first_read.unwrap_or_default()) }
+    })
+}
+
+fn blur(image: &Mat, strength: i32) -> Result<Mat, MaskMyNameError> {
+    let mut blurred: Mat = Default::default();
+    gaussian_blur(image, &mut blurred, Size::new(strength, strength), 0., 0., BORDER_DEFAULT)
+        .map_err(|e| MaskMyNameError::BlurError(e.message))?;
+    Ok(blurred)
+}
+
+/// Averages `pixel_size` blocks by shrinking, then scales back up without smoothing.
+fn pixelate(image: &Mat, config: &MaskConfig) -> Result<Mat, MaskMyNameError> {
+    let pixelate_error = |e: opencv::Error| MaskMyNameError::MaskingBarCreationError(e.message);
+    let small_size = Size::new((image.cols() / config.pixel_size).max(1), (image.rows() / config.pixel_size).max(1));
+    let mut small: Mat = Default::default();
+    resize(image, &mut small, small_size, 0., 0., config.interpolation.unwrap_or(INTER_AREA)).map_err(pixelate_error)?;
+    let mut mosaic: Mat = Default::default();
+    resize(&small, &mut mosaic, image.size().map_err(pixelate_error)?, 0., 0., config.interpolation.unwrap_or(INTER_NEAREST))
+        .map_err(pixelate_error)?;
+    Ok(mosaic)
+}
+
+/// Neighbourhood of a region that inpainting draws from, in pixels on every side.
+const INPAINT_RADIUS: i32 = 3;
+
+/// `area` of `image` filled in from the pixels around it with `config.inpaint_method`. Only a margin around it
+/// is processed rather than the whole image. An alpha channel is kept as it was.
+fn inpaint_region(image: &Mat, area: Rect, config: &MaskConfig) -> Result<Mat, MaskMyNameError> {
+    let inpaint_error = |e: opencv::Error| MaskMyNameError::InpaintError(e.message);
+    if image.depth() != CV_8U {
+        return Err(MaskMyNameError::InpaintError("only 8-bit images can be inpainted, pass --force-8bit".to_string()));
+    }
+    let around = grow(area, 2 * INPAINT_RADIUS, image.cols(), image.rows());
+    let inner = Rect::new(area.x - around.x, area.y - around.y, area.width, area.height);
+    let mut mask = Mat::new_rows_cols_with_default(around.height, around.width, CV_8UC1, Scalar::all(0.)).map_err(inpaint_error)?;
+    rectangle(&mut mask, inner, Scalar::all(255.), FILLED, LINE_8, 0).map_err(inpaint_error)?;
+    let source = Mat::roi(image, around).map_err(inpaint_error)?;
+    let mut filled: Mat = Default::default();
+    match source.channels() {
+        4 => {
+            let mut channels = VectorOfMat::new();
+            split(&source, &mut channels).map_err(inpaint_error)?;
+            let alpha = channels.get(3).map_err(inpaint_error)?;
+            let (mut color, mut inpainted): (Mat, Mat) = Default::default();
+            cvt_color(&source, &mut color, COLOR_BGRA2BGR, 0).map_err(inpaint_error)?;
+            inpaint(&color, &mask, &mut inpainted, INPAINT_RADIUS as f64, config.inpaint_method).map_err(inpaint_error)?;
+            let mut merged = VectorOfMat::new();
+            split(&inpainted, &mut merged).map_err(inpaint_error)?;
+            merged.push(alpha);
+            merge(&merged, &mut filled).map_err(inpaint_error)?;
+        },
+        _ => { inpaint(&source, &mask, &mut filled, INPAINT_RADIUS as f64, config.inpaint_method).map_err(inpaint_error)?; }
+    }
+    Mat::roi(&filled, inner).and_then(|roi| roi.try_clone()).map_err(inpaint_error)
+}
+
+/// Random values over the full range of the depth of `image`, in its size and type. An alpha channel stays opaque.
+fn noise(image: &Mat) -> Result<Mat, MaskMyNameError> {
+    let noise_error = |e: opencv::Error| MaskMyNameError::MaskingBarCreationError(e.message);
+    let max = depth_max_value(image.depth());
+    // the upper bound of randu is exclusive.
+    let high = match image.depth() {
+        CV_32F | CV_64F => { max },
+        _ => { max + 1. }
+    };
+    let (low, high) = match image.channels() {
+        4 => { (Scalar::new(0., 0., 0., max), Scalar::new(high, high, high, high)) },
+        _ => { (Scalar::all(0.), Scalar::all(high)) }
+    };
+    let mut filled = Mat::new_rows_cols_with_default(image.rows(), image.cols(), image.typ(), Scalar::all(0.)).map_err(noise_error)?;
+    randu(&mut filled, &low, &high).map_err(noise_error)?;
+    Ok(filled)
+}
+
+/// Set inside a `size` rectangle whose corners are rounded by `radius`, clamped to half the smaller side:
+/// two crossing rectangles fill all but the corners, a circle fills each corner.
+fn rounded_mask(size: Size, radius: i32) -> opencv::Result<Mat> {
+    let radius = radius.clamp(0, size.width.min(size.height) / 2);
+    let mut mask = Mat::new_rows_cols_with_default(size.height, size.width, CV_8UC1, Scalar::all(0.))?;
+    let set = Scalar::all(255.);
+    rectangle(&mut mask, Rect::new(radius, 0, size.width - 2 * radius, size.height), set, FILLED, LINE_8, 0)?;
+    rectangle(&mut mask, Rect::new(0, radius, size.width, size.height - 2 * radius), set, FILLED, LINE_8, 0)?;
+    let (right, bottom) = (size.width - 1 - radius, size.height - 1 - radius);
+    for (x, y) in [(radius, radius), (right, radius), (radius, bottom), (right, bottom)] {
+        circle(&mut mask, Point::new(x, y), radius, set, FILLED, LINE_8, 0)?;
+    }
+    Ok(mask)
+}
+
+/// Convex hull of the pixels of `text_mask` set inside `area`, as a mask the size of `area`. `None` when none is set,
+/// e.g. for a region a DNN found in colours outside the HSV bounds.
+fn hull_mask(text_mask: &Mat, area: Rect) -> opencv::Result<Option<Mat>> {
+    let roi = Mat::roi(text_mask, area)?.try_clone()?;
+    let mut contours: VectorOfVectorOfPoint = Default::default();
+    find_contours(&roi, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE, Point::new(0, 0))?;
+    let points: VectorOfPoint = contours.iter().flat_map(|contour| contour.to_vec()).collect();
+    if points.is_empty() {
+        return Ok(None);
+    }
+    let mut hull = VectorOfPoint::new();
+    convex_hull(&points, &mut hull, false, true)?;
+    let mut mask = Mat::new_rows_cols_with_default(area.height, area.width, CV_8UC1, Scalar::all(0.))?;
+    fill_convex_poly(&mut mask, &hull, Scalar::all(255.), LINE_8, 0)?;
+    Ok(Some(mask))
+}
+
+/// Regions masked in a colour that fails `config.contrast_check` are added to `low_contrast`.
+/// With a `shape` only its set pixels are masked, otherwise the whole area, with rounded corners for `config.corner_radius`.
+fn apply_mask(image: &Mat, area: Rect, mode: MaskMode, shape: Option<&Mat>, config: &MaskConfig,
+              low_contrast: &mut Vec<Rect>) -> Result<(), MaskMyNameError> {
+    let mut roi = Mat::roi(image, area).map_err(|e| MaskMyNameError::RoiCreationError(e.message))?;
+    let filled = match mode {
+        MaskMode::Blur => { blur(&roi, config.blur_strength)? },
+        MaskMode::Pixelate => { pixelate(&roi, config)? },
+        MaskMode::Strong => { pixelate(&blur(&roi, config.blur_strength)?, config)? },
+        MaskMode::Noise => { noise(&roi)? },
+        MaskMode::Inpaint => { inpaint_region(image, area, config)? },
+        _ => {
+            let (color, low) = contrasting_color(image, area, mask_color(image, area, mode)?, mode, config)?;
+            if low {
+                low_contrast.push(area);
+            }
+            masking_bar(&roi, color)?
+        }
+    };
+    match (shape, config.corner_radius) {
+        (Some(shape), _) => { filled.copy_to_masked(&mut roi, shape) },
+        (None, Some(radius)) => { rounded_mask(area.size(), radius).and_then(|mask| filled.copy_to_masked(&mut roi, &mask)) },
+        (None, None) => { filled.copy_to(&mut roi) }
+    }.map_err(|e| MaskMyNameError::MaskingBarCreationError(e.message))
+}
+
+#[cfg(feature = "interactive")]
+fn review_masks(image: &Mat, proposed: &[Rect]) -> Result<Vec<Rect>, MaskMyNameError> {
+    interactive::review(image, proposed)
+}
+
+#[cfg(not(feature = "interactive"))]
+fn review_masks(_image: &Mat, _proposed: &[Rect]) -> Result<Vec<Rect>, MaskMyNameError> {
+    Err(MaskMyNameError::InteractiveError("this build has no GUI support, rebuild with --features interactive".to_string()))
+}
+
+/// `area` grown or shrunk vertically by `scale` around its centre, clipped to `rows`. Never less than one pixel high.
+fn scale_vertically(area: Rect, scale: f64, rows: i32) -> Rect {
+    let height = ((area.height as f64 * scale).round() as i32).max(1);
+    let top = (area.y + (area.height - height) / 2).max(0);
+    let bottom = (area.y + (area.height - height) / 2 + height).min(rows);
+    Rect::new(area.x, top, area.width, (bottom - top).max(1))
+}
+
+/// `area` grown by `margin` on every side, clipped to a `cols` x `rows` image.
+fn grow(area: Rect, margin: i32, cols: i32, rows: i32) -> Rect {
+    let (left, top) = ((area.x - margin).max(0), (area.y - margin).max(0));
+    let (right, bottom) = ((area.x + area.width + margin).min(cols), (area.y + area.height + margin).min(rows));
+    Rect::new(left, top, right - left, bottom - top)
+}
+
+/// Blurs everything, then puts the original pixels of `sharp` back.
+fn blur_except(image: &Mat, sharp: &[Rect], strength: i32) -> Result<Mat, MaskMyNameError> {
+    let blurred = blur(image, strength)?;
+    for area in sharp {
+        let mut target = Mat::roi(&blurred, *area).map_err(|e| MaskMyNameError::RoiCreationError(e.message))?;
+        Mat::roi(image, *area).and_then(|original| original.copy_to(&mut target))
+            .map_err(|e| MaskMyNameError::RoiCreationError(e.message))?;
+    }
+    Ok(blurred)
+}
+
+/// The masked regions of `masked` on a transparent 8-bit BGRA canvas of the image's size,
+/// opaque and showing the masked pixels inside them, for compositing over the original elsewhere.
+pub fn mask_layer(masked: &MaskedImage) -> Result<Mat, MaskMyNameError> {
+    let layer_error = |e: opencv::Error| MaskMyNameError::MaskLayerError(e.message);
+    let mut bgra: Mat = Default::default();
+    cvt_color(&detection_input(&masked.image)?, &mut bgra, COLOR_BGR2BGRA, 0).map_err(layer_error)?;
+    let layer = Mat::new_rows_cols_with_default(bgra.rows(), bgra.cols(), CV_8UC4, Scalar::all(0.)).map_err(layer_error)?;
+    for area in &masked.masked {
+        let mut target = Mat::roi(&layer, *area).map_err(layer_error)?;
+        Mat::roi(&bgra, *area).and_then(|roi| roi.copy_to(&mut target)).map_err(layer_error)?;
+    }
+    Ok(layer)
+}
+
+/// Width of the separator between the two halves of `side_by_side`.
+const SEPARATOR_WIDTH: i32 = 4;
+
+/// The original and the masked image next to each other, as 8-bit BGR, split by a white bar.
+/// Needs `config.keep_original`.
+pub fn side_by_side(masked: &MaskedImage) -> Result<Mat, MaskMyNameError> {
+    let compare_error = |e: opencv::Error| MaskMyNameError::CompareError(e.message);
+    let original = masked.original.as_ref().ok_or(MaskMyNameError::CompareError("the original was not kept".to_string()))?;
+    let separator = Mat::new_rows_cols_with_default(masked.image.rows(), SEPARATOR_WIDTH, CV_8UC3, Scalar::all(255.)).map_err(compare_error)?;
+    let mut halves = VectorOfMat::new();
+    halves.push(detection_input(original)?);
+    halves.push(separator);
+    halves.push(detection_input(&masked.image)?);
+    let mut joined: Mat = Default::default();
+    hconcat(&halves, &mut joined).map_err(compare_error)?;
+    Ok(joined)
+}
+
+/// Per-channel absolute difference between the original and the masked image, as 8-bit BGR, black where nothing changed.
+/// With `min_change`, a single-channel binary image instead, white wherever any channel changed by more than that,
+/// which shows the faint changes of a blur that the raw difference leaves near black. Needs `config.keep_original`.
+pub fn difference(masked: &MaskedImage, min_change: Option<u8>) -> Result<Mat, MaskMyNameError> {
+    let diff_error = |e: opencv::Error| MaskMyNameError::DiffError(e.message);
+    let original = masked.original.as_ref().ok_or(MaskMyNameError::DiffError("the original was not kept".to_string()))?;
+    let mut diff: Mat = Default::default();
+    absdiff(&detection_input(original)?, &detection_input(&masked.image)?, &mut diff).map_err(diff_error)?;
+    let Some(min_change) = min_change else {
+        return Ok(diff);
+    };
+    let mut binary: Mat = Default::default();
+    threshold(&diff, &mut binary, min_change as f64, 255., THRESH_BINARY).map_err(diff_error)?;
+    let mut changed = Mat::new_rows_cols_with_default(binary.rows(), binary.cols(), CV_8UC1, Scalar::all(0.)).map_err(diff_error)?;
+    for channel in 0..binary.channels() {
+        let (mut plane, mut merged): (Mat, Mat) = Default::default();
+        extract_channel(&binary, &mut plane, channel).map_err(diff_error)?;
+        bitwise_or(&changed, &plane, &mut merged, &Mat::default()).map_err(diff_error)?;
+        changed = merged;
+    }
+    Ok(changed)
+}
+
+/// Called after each region with the number of regions done and the total.
+/// Returning `ControlFlow::Break` stops the run with `MaskMyNameError::Cancelled`.
+pub type Progress<'a> = &'a mut dyn FnMut(usize, usize) -> ControlFlow<()>;
+
+/// The part of `area` inside `bounds`, in the coordinates of `bounds`, `None` when they do not intersect.
+fn clip_to(area: &Rect, bounds: &Rect) -> Option<Rect> {
+    let (left, top) = (area.x.max(bounds.x), area.y.max(bounds.y));
+    let (right, bottom) = ((area.x + area.width).min(bounds.x + bounds.width), (area.y + area.height).min(bounds.y + bounds.height));
+    (left < right && top < bottom).then(|| Rect::new(left - bounds.x, top - bounds.y, right - left, bottom - top))
+}
+
+/// `mask_whole` on `config.crop` of `image` only, when set, with every region returned in the coordinates of the full image.
+/// Zones and `Detector::Boxes` are clipped to the crop, those outside it are dropped.
+fn mask_regions(passes: &mut [OcrPass], mut image: Mat, label: &str, config: &MaskConfig,
+                progress: Progress) -> Result<MaskedImage, MaskMyNameError> {
+    let Some(crop) = config.crop else {
+        return mask_whole(passes, image, label, config, progress);
+    };
+    check_zones(&image, &[crop])?;
+    let crop_error = |e: opencv::Error| MaskMyNameError::RoiCreationError(e.message);
+    // converted here rather than for the crop alone, which has to fit back in.
+    if config.force_8bit {
+        image = to_8bit(&image)?;
+    }
+    let original = match config.keep_original {
+        true => { Some(image.try_clone().map_err(|e| MaskMyNameError::CompareError(e.message))?) },
+        false => { None }
+    };
+    let mut cropped = config.clone();
+    cropped.crop = None;
+    cropped.force_8bit = false;
+    cropped.keep_original = false;
+    cropped.include_zones = config.include_zones.iter().filter_map(|zone| clip_to(zone, &crop)).collect();
+    if !config.include_zones.is_empty() && cropped.include_zones.is_empty() {
+        return Err(MaskMyNameError::RegionOutOfBounds(format!("no include zone overlaps the crop {}", format_rect(&crop))));
+    }
+    if let Detector::Boxes(boxes) = &config.detector {
+        cropped.detector = Detector::Boxes(boxes.iter().filter_map(|area| clip_to(area, &crop)).collect());
+    }
+    let part = Mat::roi(&image, crop).and_then(|roi| roi.try_clone()).map_err(crop_error)?;
+    let mut masked = mask_whole(passes, part, label, &cropped, progress)?;
+    Mat::roi(&image, crop).and_then(|mut roi| masked.image.copy_to(&mut roi)).map_err(crop_error)?;
+    let shift = |area: &mut Rect| {
+        area.x += crop.x;
+        area.y += crop.y;
+    };
+    masked.matches.iter_mut().for_each(|m| shift(&mut m.area));
+    masked.masked.iter_mut().for_each(shift);
+    masked.regions.iter_mut().for_each(|(area, _)| shift(area));
+    masked.codes.iter_mut().for_each(|code| shift(&mut code.area));
+    masked.low_contrast.iter_mut().for_each(shift);
+    masked.image = image;
+    masked.original = original;
+    Ok(masked)
+}
+
+/// Detection, OCR and masking of an image already in memory. `label` names the exported crops.
+/// Finding no match is not an error here, callers decide what that means.
+fn mask_whole(passes: &mut [OcrPass], mut image: Mat, label: &str, config: &MaskConfig,
+              progress: Progress) -> Result<MaskedImage, MaskMyNameError> {
+    // the noise mask mode draws from this RNG.
+    if let Some(seed) = config.seed {
+        set_rng_seed(seed).map_err(|e| MaskMyNameError::InvalidArgument(format!("seed {}: {}", seed, e.message)))?;
+    }
+    if config.force_8bit {
+        image = to_8bit(&image)?;
+    }
+    let original = match config.keep_original {
+        true => { Some(image.try_clone().map_err(|e| MaskMyNameError::CompareError(e.message))?) },
+        false => { None }
+    };
+    let mut timings = Timings::default();
+    let mut detector_counts = Vec::new();
+    let areas = match &config.tiling {
+        None => { find_textarea(&image, config, &mut timings, &mut detector_counts)? },
+        Some(tiling) => { find_textarea_tiled(&image, tiling, config, &mut timings, &mut detector_counts)? }
+    };
+    let codes = match config.mask_qr {
+        true => { find_codes(&detection_input(&image)?).map_err(|e| MaskMyNameError::QrDetectionError(e.message))? },
+        false => { Vec::new() }
+    };
+    let mut matches = Vec::new();
+    // codes are masked whatever `invert_mask` says, and blurred along with the background.
+    let mut masked: Vec<Rect> = match config.blur_background {
+        Some(_) => { Vec::new() },
+        None => { codes.iter().map(|code| code.area).collect() }
+    };
+    let mut sharp = Vec::new();
+    let retries_before: usize = passes.iter().map(|pass| pass.retries).sum();
+    // regions whose target has a mask mode of its own.
+    let mut styled: Vec<(Rect, MaskMode)> = Vec::new();
+    let mut regions = Vec::new();
+    let mut capped = false;
+    let mut flat_regions = 0;
+    let total = areas.len();
+    let rescans_before: usize = passes.iter().map(|pass| pass.rescans).sum();
+    let ocr_started = Instant::now();
+    for (index, area) in areas.into_iter().enumerate() {
+        let target_image = Mat::roi(&image, grow(area, config.ocr_margin, image.cols(), image.rows()))
+            .map_err(|e| MaskMyNameError::RoiCreationError(e.message))
+            .and_then(|roi| detection_input(&roi))?;
+        // dropped like a region outside the zones, neither read, masked nor inverted.
+        if let (Some(min), false) = (config.min_region_contrast, config.masks_everything()) {
+            if region_contrast(&target_image).map_err(|e| MaskMyNameError::MaskTextError(e.message))? < min {
+                flat_regions += 1;
+                if progress(index + 1, total).is_break() {
+                    return Err(MaskMyNameError::Cancelled());
+                }
+                continue;
+            }
+        }
+        let (matched, denied) = match match_region(passes, &target_image, area, config)? {
+            Verdict::Matched(text, target, score) => {
+                regions.push((area, text.clone()));
+                (Some((text, target, score)), false)
+            },
+            Verdict::Unmatched(text) => {
+                regions.push((area, text));
+                (None, false)
+            },
+            Verdict::Denied(text) => {
+                regions.push((area, text));
+                (None, true)
+            }
+        };
+        if let Some(crops_dir) = &config.crops_dir {
+            if matched.is_some() || !config.crops_matched_only {
+                let roi = Mat::roi(&image, area).map_err(|e| MaskMyNameError::RoiCreationError(e.message))?;
+                write_crop(crops_dir, label, index, area, &roi)?;
+            }
+        }
+        match config.blur_background {
+            Some(SharpRegions::Detected) => { sharp.push(area); },
+            Some(SharpRegions::Matched) => {
+                if matched.is_some() || denied {
+                    sharp.push(area);
+                }
+            },
+            None => {
+                if !denied && matched.is_some() != config.invert_mask {
+                    let bar = scale_vertically(area, config.bar_scale, image.rows());
+                    if let Some((_, target, _)) = &matched {
+                        styled.push((bar, config.mask_mode_for(target)));
+                    }
+                    masked.push(bar);
+                }
+            }
+        }
+        if let Some((text, target, score)) = matched {
+            matches.push(RegionMatch { area, text, target, score });
+            if config.max_matches.map(|max| matches.len() >= max).unwrap_or(false) {
+                capped = true;
+                break;
+            }
+        }
+        if progress(index + 1, total).is_break() {
+            return Err(MaskMyNameError::Cancelled());
+        }
+    }
+    timings.ocr = ocr_started.elapsed();
+    let rescanned = passes.iter().map(|pass| pass.rescans).sum::<usize>() - rescans_before;
+    if config.interactive {
+        masked = review_masks(&image, &masked)?;
+    }
+    // masking only once every region is read keeps OCR and crops on the original pixels where regions overlap.
+    let mask_modes: Vec<MaskMode> = masked.iter()
+        .map(|area| styled.iter().find(|(styled, _)| styled == area).map(|(_, mode)| *mode).unwrap_or(config.mask_mode))
+        .collect();
+    // traced before any mask is drawn, so overlapping regions see the text of each other.
+    let shapes: Vec<Option<Mat>> = match config.hull {
+        true => {
+            let text_mask = mask_text(&image, config)?;
+            masked.iter().map(|area| hull_mask(&text_mask, *area)).collect::<opencv::Result<_>>()
+                .map_err(|e| MaskMyNameError::MaskingBarCreationError(e.message))?
+        },
+        false => { masked.iter().map(|_| None).collect() }
+    };
+    let mut low_contrast = Vec::new();
+    for ((area, mode), shape) in masked.iter().zip(&mask_modes).zip(&shapes) {
+        apply_mask(&image, *area, *mode, shape.as_ref(), config, &mut low_contrast)?;
+    }
+    if config.verify && !config.masks_everything() {
+        verify_masks(passes, &image, &masked, config)?;
+    }
+    if config.blur_background.is_some() {
+        image = blur_except(&image, &sharp, config.blur_strength)?;
+    }
+    // drawn last so verification never reads it, and only on images that had something redacted.
+    if let (Some(notice), true) = (&config.stamp, !masked.is_empty() || !matches.is_empty()) {
+        stamp::draw_stamp(&mut image, notice, &masked)?;
+    }
+    let target_counts = passes.iter().flat_map(|pass| pass.targets.iter())
+        .map(|t| (t.name.clone(), matches.iter().filter(|m: &&RegionMatch| m.target == t.name).count()))
+        .collect();
+    let ocr_retried = passes.iter().map(|pass| pass.retries).sum::<usize>() - retries_before;
+    Ok(MaskedImage { image, original, matches, masked, mask_modes, regions, codes, target_counts, detector_counts, low_contrast, relaxed: None, capped, rescanned, flat_regions, ocr_retried, timings })
+}
+
+/// Added to the hue, saturation and value upper bounds per relaxation attempt.
+const RELAX_HSV_STEP: f64 = 40.;
+
+/// `config` loosened for relaxation `attempt` of an image with `rows` rows: the upper HSV bounds, or the default
+/// dark-pixel bound, widened by `RELAX_HSV_STEP` per attempt and the minimum region height halved per attempt.
+fn relaxed(config: &MaskConfig, rows: i32, attempt: u32) -> (MaskConfig, Relaxation) {
+    let widened_by = RELAX_HSV_STEP * attempt as f64;
+    // hue is 0-180 in OpenCV, saturation and value 0-255.
+    let widen = |upper: Scalar| Scalar::new((upper[0] + widened_by).min(180.), (upper[1] + widened_by).min(255.),
+                                            (upper[2] + widened_by).min(255.), upper[3]);
+    let min_height = config.min_height.unwrap_or(rows / config.min_height_divisor) >> attempt;
+    let mut relaxed = config.clone();
+    relaxed.hsv_upper = Some(widen(config.hsv_upper.unwrap_or(Scalar::new(0., 0., max_range(rows, config.sensitivity), 255.))));
+    relaxed.hsv_ranges = config.hsv_ranges.iter().map(|(lower, upper)| (*lower, widen(*upper))).collect();
+    relaxed.min_height = Some(min_height);
+    (relaxed, Relaxation { attempt, hsv_widened_by: widened_by, min_height })
+}
+
+pub fn mask_my_name(image_path: &Path, target_strings: &[String], config: &MaskConfig) -> Result<MaskedImage, MaskMyNameError> {
+    mask_my_name_with_progress(image_path, target_strings, config, &mut |_, _| ControlFlow::Continue(()))
+}
+
+/// `mask_my_name` reporting each OCR'd region to `progress`, which can cancel the run.
+pub fn mask_my_name_with_progress(image_path: &Path, target_strings: &[String], config: &MaskConfig,
+                                  progress: Progress) -> Result<MaskedImage, MaskMyNameError> {
+    mask_my_name_with_coverage(image_path, target_strings, config, progress).and_then(|masked| check_coverage(masked, config))
+}
+
+/// `mask_my_name_with_progress` without the coverage check, so an image without a match comes back unmasked
+/// with its `regions` for a look at what was read. Pass it to `check_coverage` before writing it.
+pub fn mask_my_name_with_coverage(image_path: &Path, target_strings: &[String], config: &MaskConfig,
+                                  progress: Progress) -> Result<MaskedImage, MaskMyNameError> {
+    let started = Instant::now();
+    let image = load_image(image_path, config)?;
+    let load = started.elapsed();
+    let mut masked = mask_loaded(image, stem_of(image_path), target_strings, config, progress)?;
+    masked.timings.load = load;
+    Ok(masked)
+}
+
+/// Everything after loading, the `config.relax_attempts` retries included.
+fn mask_loaded(image: Mat, label: &str, target_strings: &[String], config: &MaskConfig,
+               progress: Progress) -> Result<MaskedImage, MaskMyNameError> {
+    let mut passes = build_passes(target_strings, config)?;
+    let unmasked = match config.relax_attempts {
+        0 => { None },
+        _ => { Some(image.try_clone().map_err(|e| MaskMyNameError::MaskTextError(e.message))?) }
+    };
+    let mut masked = mask_regions(&mut passes, image, label, config, &mut *progress)?;
+    if let Some(unmasked) = unmasked {
+        for attempt in 1..=config.relax_attempts {
+            if masked.coverage() != Coverage::NoMatch {
+                break;
+            }
+            let (relaxed_config, relaxation) = relaxed(config, unmasked.rows(), attempt);
+            let image = unmasked.try_clone().map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+            masked = mask_regions(&mut passes, image, label, &relaxed_config, &mut *progress)?;
+            masked.relaxed = Some(relaxation);
+        }
+    }
+    Ok(masked)
+}
+
+/// Name of the crops of an image masked from memory, see `MaskConfig::crops_dir`.
+const MAT_LABEL: &str = "image";
+
+/// `mask_my_name` for an image already in memory, e.g. a frame from a video decoder, so nothing is read or written.
+/// `image` may be anything `imread` returns: 8-bit, 16-bit or float, grey, BGR or BGRA. It is only read: its pixels are
+/// copied first, since a `Mat` shares them with its clones, and the copy is masked and returned in `MaskedImage::image`.
+/// `config.auto_orient` does not apply, there is no EXIF data.
+pub fn mask_mat(image: &Mat, target_strings: &[String], config: &MaskConfig) -> Result<MaskedImage, MaskMyNameError> {
+    mask_mat_with_coverage(image, target_strings, config, &mut |_, _| ControlFlow::Continue(())).and_then(|masked| check_coverage(masked, config))
+}
+
+/// `mask_mat` reporting to `progress` and without the coverage check, like `mask_my_name_with_coverage`.
+pub fn mask_mat_with_coverage(image: &Mat, target_strings: &[String], config: &MaskConfig,
+                              progress: Progress) -> Result<MaskedImage, MaskMyNameError> {
+    let image = image.try_clone().map_err(|e| MaskMyNameError::MaskTextError(e.message))?;
+    mask_loaded(image, MAT_LABEL, target_strings, config, progress)
+}
+
+/// A sign that detection or matching likely missed something, see `suspicions`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Suspicion {
+    /// Nothing was detected although this share of the pixels are edges, as on text-heavy images.
+    NoRegions(f64),
+    /// The target matched a single region while these other ones read something close to it.
+    NearMisses(String, Vec<Rect>),
+}
+
+/// Share of Canny edge pixels above which an image is taken to be full of text.
+const BUSY_EDGE_DENSITY: f64 = 0.05;
+/// Similarity to a target, as in fuzzy mode, from which an unmatched region counts as a near miss.
+const NEAR_MISS_SIMILARITY: f64 = 0.6;
+
+/// Checks the statistics of a run for likely under-redaction: no regions at all on an image with many edges,
+/// or a target matched once next to regions that read almost like it, hinting at more occurrences OCR garbled.
+pub fn suspicions(masked: &MaskedImage, config: &MaskConfig) -> Result<Vec<Suspicion>, MaskMyNameError> {
+    let mut found = Vec::new();
+    if masked.regions.is_empty() {
+        let edge_error = |e: opencv::Error| MaskMyNameError::MaskTextError(e.message);
+        let mut gray: Mat = Default::default();
+        cvt_color(&detection_input(&masked.image)?, &mut gray, COLOR_BGR2GRAY, 0).map_err(edge_error)?;
+        let mut edges: Mat = Default::default();
+        canny(&gray, &mut edges, 100., 200., 3, false).map_err(edge_error)?;
+        let density = count_non_zero(&edges).map_err(edge_error)? as f64 / (gray.rows() * gray.cols()).max(1) as f64;
+        if density > BUSY_EDGE_DENSITY {
+            found.push(Suspicion::NoRegions(density));
+        }
+    }
+    if config.masks_everything() {
+        return Ok(found);
+    }
+    for (target, _) in masked.target_counts.iter().filter(|(_, count)| *count == 1) {
+        let target_text = normalize_text(target);
+        let near: Vec<Rect> = masked.regions.iter()
+            .filter(|(area, _)| !masked.matches.iter().any(|m| m.area == *area))
+            .filter(|(_, text)| similarity(&normalize_text(text), &target_text) >= NEAR_MISS_SIMILARITY)
+            .map(|(area, _)| *area)
+            .collect();
+        if !near.is_empty() {
+            found.push(Suspicion::NearMisses(target.clone(), near));
+        }
+    }
+    Ok(found)
+}
+
+/// `NoMatchingString` without any match, `MissingTargetsError` for a partial match with `config.require_all`.
+pub fn check_coverage(masked: MaskedImage, config: &MaskConfig) -> Result<MaskedImage, MaskMyNameError> {
+    match masked.coverage() {
+        Coverage::NoMatch => { Err(MaskMyNameError::NoMatchingString()) },
+        Coverage::Partial if config.require_all => { Err(MaskMyNameError::MissingTargetsError(masked.missing_targets().join(", "))) },
+        _ => { Ok(masked) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::{absdiff, Vec3b, CV_8UC3};
+    use opencv::imgproc::{rectangle, CHAIN_APPROX_NONE, FILLED, LINE_8};
+    use opencv::photo::INPAINT_NS;
+    use super::*;
+
+    /// A white `cols` x `rows` BGR image with black `boxes`, standing in for dark text on a light background.
+    fn dark_boxes_on_white(cols: i32, rows: i32, boxes: &[Rect]) -> Mat {
+        let mut image = Mat::new_rows_cols_with_default(rows, cols, CV_8UC3, Scalar::all(255.)).unwrap();
+        for area in boxes {
+            rectangle(&mut image, *area, Scalar::all(0.), FILLED, LINE_8, 0).unwrap();
+        }
+        image
+    }
+
+    #[test]
+    fn numeric_mode_ignores_separators() {
+        let config = MaskConfig { match_mode: MatchMode::Numeric, ..Default::default() };
+        assert_eq!(digits("555-123-4567"), "5551234567");
+        for text in ["call 555-123-4567", "call 555.123.4567", "call 5551234567", "call (555) 123 4567"] {
+            assert!(variant_matches(text, "555-123-4567", &config), "{}", text);
+            assert!(variant_matches(text, "5551234567", &config), "{}", text);
+        }
+        assert!(!variant_matches("call 555-123-4568", "555-123-4567", &config));
+        // a target without digits would match any text.
+        assert!(!variant_matches("call 555-123-4567", "phone", &config));
+    }
+
+    #[test]
+    fn exact_mode_needs_the_whole_text_to_equal_the_target() {
+        let substring = MaskConfig::default();
+        let exact = MaskConfig { match_mode: MatchMode::Exact, ..Default::default() };
+        assert!(variant_matches("name: jane doe\n", "jane doe", &substring));
+        assert!(!variant_matches("name: jane doe\n", "jane doe", &exact));
+        // the trailing newline Tesseract adds is not part of the text.
+        assert!(variant_matches("jane doe\n", "jane doe", &exact));
+        assert!(!variant_matches("jane doe\n", "jane", &exact));
+    }
+
+    #[test]
+    fn short_regions_pass_a_lowered_height_threshold_only() {
+        // 8 pixels tall in a 720 row image, under the default minimum of 720 / 72 = 10.
+        let caption = Rect::new(0, 0, 60, 8);
+        assert!(!keeps_region(&caption, 1000, 720, &MaskConfig::default()));
+        assert!(keeps_region(&caption, 1000, 720, &MaskConfig { min_height_divisor: 120, ..Default::default() }));
+        assert!(keeps_region(&caption, 1000, 720, &MaskConfig { min_height: Some(6), ..Default::default() }));
+        // the height has to be above the minimum, not at it.
+        assert!(!keeps_region(&caption, 1000, 720, &MaskConfig { min_height: Some(8), ..Default::default() }));
+    }
+
+    #[test]
+    fn aspect_ratio_is_compared_as_a_float() {
+        let config = MaskConfig::default();
+        // 20 pixels tall, well above the minimum height of 720 / 72.
+        assert!(keeps_region(&Rect::new(0, 0, 290, 20), 1000, 720, &config));
+        assert!(!keeps_region(&Rect::new(0, 0, 300, 20), 1000, 720, &config));
+        assert!(!keeps_region(&Rect::new(0, 0, 310, 20), 1000, 720, &config));
+    }
+
+    #[test]
+    fn bar_scale_resizes_masks_around_their_centre() {
+        let area = Rect::new(10, 40, 100, 20);
+        assert_eq!(scale_vertically(area, 1., 200), area);
+        assert_eq!(scale_vertically(area, 2., 200), Rect::new(10, 30, 100, 40));
+        assert_eq!(scale_vertically(area, 1.5, 200), Rect::new(10, 35, 100, 30));
+        assert_eq!(scale_vertically(area, 0.5, 200), Rect::new(10, 45, 100, 10));
+        // clipped to the image at the top and the bottom.
+        assert_eq!(scale_vertically(Rect::new(10, 5, 100, 20), 2., 200), Rect::new(10, 0, 100, 35));
+        assert_eq!(scale_vertically(area, 2., 55), Rect::new(10, 30, 100, 25));
+        assert_eq!(scale_vertically(area, 0.01, 200).height, 1);
+    }
+
+    #[test]
+    fn tiles_overlap_and_their_spans_cover_the_axis_once() {
+        let tiling = Tiling { size: 400, overlap: 100 };
+        assert_eq!(tile_spans(1000, &tiling), vec![(0, 400, 0, 350), (300, 400, 350, 650), (600, 400, 650, 1000)]);
+        // the last tile is cut short at the edge and owns everything up to it.
+        let spans = tile_spans(1100, &tiling);
+        assert_eq!(spans.last(), Some(&(900, 200, 950, 1100)));
+        for pair in spans.windows(2) {
+            assert_eq!(pair[0].0 + pair[0].1 - pair[1].0, tiling.overlap);
+            assert_eq!(pair[0].3, pair[1].2);
+        }
+        assert_eq!(tile_spans(300, &tiling), vec![(0, 300, 0, 300)]);
+    }
+
+    #[test]
+    fn targets_shorter_than_the_min_token_length_are_skipped() {
+        let targets = vec!["an".to_string(), "jane_doe".to_string()];
+        let config = MaskConfig { min_token_length: 3, ..Default::default() };
+        let built = build_targets(&targets, &config);
+        assert!(built[0].variants.is_empty());
+        assert_eq!(built[1].variants, ["jane_doe", "jane doe"]);
+        assert_eq!(best_match("an example", &built, &config), None);
+        assert_eq!(best_match("by jane doe", &built, &config), Some((1, 1.)));
+        // the default keeps every variant.
+        assert_eq!(build_targets(&targets, &MaskConfig::default())[0].variants, ["an"]);
+    }
+
+    #[test]
+    fn closing_joins_boxes_further_apart_than_dilation_bridges() {
+        // a 30 pixel gap, wider than dilation bridges in a 300 row image.
+        let image = dark_boxes_on_white(400, 300, &[Rect::new(20, 100, 40, 20), Rect::new(90, 100, 40, 20)]);
+        let config = MaskConfig::default();
+        assert_eq!(find_textarea_from_mask(&mask_text(&image, &config).unwrap(), &config).unwrap().len(), 2);
+        let closed = MaskConfig { close_kernel: Some(Size::new(35, 3)), ..Default::default() };
+        assert_eq!(find_textarea_from_mask(&mask_text(&image, &closed).unwrap(), &closed).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn confusables_map_to_the_first_character_of_their_group() {
+        let groups = vec!["o0".to_string(), "l1".to_string()];
+        assert_eq!(canonicalize("l00k", &groups), "look");
+        assert_eq!(canonicalize("l00k", &[]), "l00k");
+        let defaults: Vec<String> = DEFAULT_CONFUSABLES.iter().map(|g| g.to_string()).collect();
+        assert_eq!(canonicalize("l00k", &defaults), canonicalize("look", &defaults));
+    }
+
+    #[test]
+    fn denylisted_text_is_refused_even_when_it_matches() {
+        let config = MaskConfig { never_mask: vec!["CONFIDENTIAL".to_string()], ..Default::default() };
+        let targets = build_targets(&["confidential".to_string(), "jane".to_string()], &config);
+        assert!(is_denied("confidential\n", &config));
+        assert!(best_match("confidential\n", &targets, &config).is_some());
+        // the entry only has to be contained, like a target.
+        assert!(is_denied("jane - confidential", &config));
+        assert!(!is_denied("jane doe", &config));
+        assert!(!is_denied("confidential", &MaskConfig::default()));
+        let blank = MaskConfig { never_mask: vec!["  ".to_string()], ..Default::default() };
+        assert!(!is_denied("jane doe", &blank));
+    }
+
+    #[test]
+    fn rounded_masks_leave_the_corners_and_clamp_the_radius() {
+        let mask = rounded_mask(Size::new(100, 40), 10).unwrap();
+        assert_eq!(*mask.at_2d::<u8>(0, 0).unwrap(), 0);
+        assert_eq!(*mask.at_2d::<u8>(39, 99).unwrap(), 0);
+        assert_eq!(*mask.at_2d::<u8>(0, 50).unwrap(), 255);
+        assert_eq!(*mask.at_2d::<u8>(20, 0).unwrap(), 255);
+        // bars are white, so a black image shows what was drawn.
+        let image = Mat::new_rows_cols_with_default(100, 200, CV_8UC3, Scalar::all(0.)).unwrap();
+        let config = MaskConfig { corner_radius: Some(10), ..Default::default() };
+        apply_mask(&image, Rect::new(20, 20, 100, 40), config.mask_mode, None, &config, &mut Vec::new()).unwrap();
+        assert_eq!(image.at_2d::<Vec3b>(20, 20).unwrap()[0], 0);
+        assert_eq!(image.at_2d::<Vec3b>(59, 119).unwrap()[0], 0);
+        assert_eq!(image.at_2d::<Vec3b>(40, 70).unwrap()[0], 255);
+        assert_eq!(image.at_2d::<Vec3b>(20, 70).unwrap()[0], 255);
+        // a radius beyond half the height is clamped, the ends becoming half circles.
+        let clamped = rounded_mask(Size::new(100, 40), 100).unwrap();
+        assert_eq!(*clamped.at_2d::<u8>(20, 5).unwrap(), 255);
+        assert_eq!(*clamped.at_2d::<u8>(0, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn ocr_margin_grows_regions_within_the_image() {
+        assert_eq!(grow(Rect::new(50, 40, 100, 20), 0, 400, 300), Rect::new(50, 40, 100, 20));
+        assert_eq!(grow(Rect::new(50, 40, 100, 20), 4, 400, 300), Rect::new(46, 36, 108, 28));
+        // clipped at every edge.
+        assert_eq!(grow(Rect::new(2, 1, 100, 20), 4, 400, 300), Rect::new(0, 0, 106, 25));
+        assert_eq!(grow(Rect::new(290, 276, 110, 24), 4, 400, 300), Rect::new(286, 272, 114, 28));
+    }
+
+    #[test]
+    fn region_area_band_includes_its_bounds() {
+        // 1200 square pixels.
+        let area = Rect::new(0, 0, 60, 20);
+        let band = |min, max| MaskConfig { min_region_area: min, max_region_area: max, ..Default::default() };
+        assert!(keeps_region(&area, 1000, 720, &band(None, None)));
+        assert!(keeps_region(&area, 1000, 720, &band(Some(1200), Some(1200))));
+        assert!(!keeps_region(&area, 1000, 720, &band(Some(1201), None)));
+        assert!(!keeps_region(&area, 1000, 720, &band(None, Some(1199))));
+    }
+
+    #[test]
+    fn stripped_digits_let_a_name_match_across_page_numbers() {
+        assert_eq!(strip_digits("jane 12 doe"), "jane doe");
+        let targets = build_targets(&["jane doe".to_string()], &MaskConfig::default());
+        let config = MaskConfig::default();
+        assert_eq!(best_match("page 3 jane 12 doe", &targets, &config), None);
+        assert_eq!(best_match(&strip_digits("page 3 jane 12 doe"), &targets, &config), Some((0, 1.)));
+    }
+
+    #[test]
+    fn prefix_and_suffix_modes_anchor_the_target() {
+        let prefix = MaskConfig { match_mode: MatchMode::Prefix, ..Default::default() };
+        let suffix = MaskConfig { match_mode: MatchMode::Suffix, ..Default::default() };
+        assert!(variant_matches("id: 12345\n", "id:", &prefix));
+        assert!(!variant_matches("your id: 12345", "id:", &prefix));
+        assert!(variant_matches("ref 12345-x\n", "-x", &suffix));
+        assert!(!variant_matches("12345-x ref", "-x", &suffix));
+        // a target longer than the text, and an empty one, match nothing.
+        assert!(!variant_matches("id", "id: 12345", &prefix));
+        assert!(!variant_matches("id", "id: 12345", &suffix));
+        assert!(!variant_matches("id: 12345", "", &prefix));
+        assert!(!variant_matches("id: 12345", "", &suffix));
+        // the whole text is both a prefix and a suffix of itself.
+        assert!(variant_matches("id", "id", &prefix));
+        assert!(variant_matches("id", "id", &suffix));
+    }
+
+    #[test]
+    fn noise_fills_only_the_region_and_repeats_under_a_seed() {
+        let mean_difference = |a: &Mat, b: &Mat| {
+            let mut diff: Mat = Default::default();
+            absdiff(a, b, &mut diff).unwrap();
+            let channel_means = mean(&diff, &Mat::default()).unwrap();
+            (0..3).map(|i| channel_means[i]).sum::<f64>() / 3.
+        };
+        let gray = Mat::new_rows_cols_with_default(40, 100, CV_8UC3, Scalar::all(128.)).unwrap();
+        set_rng_seed(7).unwrap();
+        let first = noise(&gray).unwrap();
+        set_rng_seed(7).unwrap();
+        let second = noise(&gray).unwrap();
+        // uniform noise is on average 64 levels away from mid grey.
+        assert!(mean_difference(&first, &gray) > 30.);
+        assert_eq!(mean_difference(&first, &second), 0.);
+        let image = dark_boxes_on_white(200, 100, &[Rect::new(20, 20, 100, 40)]);
+        let original = image.try_clone().unwrap();
+        apply_mask(&image, Rect::new(20, 20, 100, 40), MaskMode::Noise, None, &MaskConfig::default(), &mut Vec::new()).unwrap();
+        let masked = Rect::new(20, 20, 100, 40);
+        assert!(mean_difference(&Mat::roi(&image, masked).unwrap(), &Mat::roi(&original, masked).unwrap()) > 30.);
+        // nothing outside the region changes.
+        let outside = Rect::new(0, 70, 200, 30);
+        assert_eq!(mean_difference(&Mat::roi(&image, outside).unwrap(), &Mat::roi(&original, outside).unwrap()), 0.);
+    }
+
+    #[test]
+    fn both_contour_approximations_give_the_same_bounding_rects() {
+        let image = dark_boxes_on_white(400, 300, &[Rect::new(20, 40, 60, 20), Rect::new(150, 40, 90, 14), Rect::new(30, 200, 120, 30)]);
+        let simple = MaskConfig { contour_approx: CHAIN_APPROX_SIMPLE, ..Default::default() };
+        let none = MaskConfig { contour_approx: CHAIN_APPROX_NONE, ..Default::default() };
+        let text_mask = mask_text(&image, &simple).unwrap();
+        let regions = find_textarea_from_mask(&text_mask, &simple).unwrap();
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions, find_textarea_from_mask(&text_mask, &none).unwrap());
+    }
+
+    #[test]
+    fn ocr_preprocessing_spreads_a_low_contrast_crop_to_black_and_white() {
+        // text 15 grey levels above its background.
+        let mut crop = Mat::new_rows_cols_with_default(40, 100, CV_8UC3, Scalar::all(120.)).unwrap();
+        rectangle(&mut crop, Rect::new(20, 10, 60, 20), Scalar::all(135.), FILLED, LINE_8, 0).unwrap();
+        let gray = preprocess_crop(&crop, OcrPreprocess::Grayscale).unwrap();
+        assert_eq!(gray.channels(), 1);
+        assert_eq!(*gray.at_2d::<u8>(0, 0).unwrap(), 120);
+        for preprocess in [OcrPreprocess::Otsu, OcrPreprocess::Stretch] {
+            let processed = preprocess_crop(&crop, preprocess).unwrap();
+            assert_eq!(*processed.at_2d::<u8>(0, 0).unwrap(), 0, "{:?}", preprocess);
+            assert_eq!(*processed.at_2d::<u8>(20, 50).unwrap(), 255, "{:?}", preprocess);
+        }
+    }
+
+    #[test]
+    fn flat_and_faint_regions_have_less_contrast_than_text() {
+        let flat = Mat::new_rows_cols_with_default(40, 100, CV_8UC3, Scalar::all(200.)).unwrap();
+        assert_eq!(region_contrast(&flat).unwrap(), 0.);
+        // 30% of the pixels black, the rest white: 255 * sqrt(0.3 * 0.7).
+        let text = dark_boxes_on_white(100, 40, &[Rect::new(20, 10, 60, 20)]);
+        assert!((region_contrast(&text).unwrap() - 116.9).abs() < 0.5);
+        let mut faint = Mat::new_rows_cols_with_default(40, 100, CV_8UC3, Scalar::all(120.)).unwrap();
+        rectangle(&mut faint, Rect::new(20, 10, 60, 20), Scalar::all(135.), FILLED, LINE_8, 0).unwrap();
+        assert!(region_contrast(&faint).unwrap() < 10.);
+    }
+
+    #[test]
+    fn telea_and_navier_stokes_inpainting_both_remove_the_text() {
+        let text = Rect::new(50, 40, 60, 20);
+        let area = grow(text, 2, 200, 100);
+        for method in [INPAINT_TELEA, INPAINT_NS] {
+            let image = dark_boxes_on_white(200, 100, &[text]);
+            let config = MaskConfig { inpaint_method: method, ..Default::default() };
+            apply_mask(&image, area, MaskMode::Inpaint, None, &config, &mut Vec::new()).unwrap();
+            // the white surroundings fill in the black text.
+            let channel_means = mean(&Mat::roi(&image, text).unwrap(), &Mat::default()).unwrap();
+            assert!((0..3).all(|i| channel_means[i] > 200.), "{} {:?}", method, channel_means);
+        }
+    }
+
+    #[test]
+    fn glob_wildcards_match_runs_and_single_characters() {
+        assert!(glob_matches("invoice-0042", "invoice-*"));
+        assert!(glob_matches("see invoice-0042 below", "invoice-*"));
+        assert!(glob_matches("id a1c", "a?c"));
+        assert!(!glob_matches("id ac", "a?c"));
+        assert!(glob_matches("jane doe", "*doe"));
+        assert!(glob_matches("jane doe", "jane*"));
+        assert!(glob_matches("jane doe", "*"));
+        assert!(glob_matches("a*b", "a\\*b"));
+        assert!(!glob_matches("aXb", "a\\*b"));
+    }
+
+    #[test]
+    fn glob_patterns_longer_than_the_text_do_not_match() {
+        assert!(!glob_matches("abc", "abcd"));
+        assert!(!glob_matches("abc", "abc?"));
+        assert!(!glob_matches("", "a"));
+        assert!(glob_matches("abc", "abc*"));
+    }
+
+    #[test]
+    fn glob_backtracks_to_the_last_star() {
+        assert!(glob_matches("aXbYb", "a*b"));
+        // the first b is followed by Y then b, not the c the pattern needs.
+        assert!(glob_matches("aXbYbZc", "a*b?c"));
+        assert!(!glob_matches("aXbYbZd", "a*b?c"));
+    }
+
+    #[test]
+    fn glob_variants_need_a_literal_character() {
+        let config = MaskConfig { match_mode: MatchMode::Glob, ..Default::default() };
+        assert!(variant_matches("invoice-0042", "invoice-*", &config));
+        assert!(!variant_matches("invoice-0042", "*", &config));
+        assert!(!variant_matches("invoice-0042", "?*?", &config));
+        assert!(variant_matches("a*b", "\\*", &config));
+    }
+
+    #[test]
+    fn default_sensitivity_keeps_the_old_detection_settings() {
+        assert_eq!(sensitivity_factor(DEFAULT_SENSITIVITY), 1.);
+        assert_eq!(sensitivity_min_height_divisor(DEFAULT_SENSITIVITY), 72);
+        assert_eq!(max_range(720, DEFAULT_SENSITIVITY), 30.);
+        assert_eq!(max_range(480, DEFAULT_SENSITIVITY), 80.);
+        assert_eq!(iterations(480, DEFAULT_SENSITIVITY), 3);
+        assert_eq!(iterations(720, DEFAULT_SENSITIVITY), 5);
+    }
+
+    #[test]
+    fn sensitivity_scales_detection_settings_to_both_ends() {
+        assert_eq!(sensitivity_min_height_divisor(0), 36);
+        assert_eq!(sensitivity_min_height_divisor(MAX_SENSITIVITY), 108);
+        assert_eq!(max_range(720, 0), 15.);
+        assert_eq!(max_range(480, 0), 40.);
+        assert_eq!(max_range(720, MAX_SENSITIVITY), 45.);
+        assert_eq!(max_range(480, MAX_SENSITIVITY), 120.);
+        assert_eq!(iterations(480, 0), 1);
+        assert_eq!(iterations(720, 0), 3);
+        assert_eq!(iterations(480, MAX_SENSITIVITY), 5);
+        assert_eq!(iterations(720, MAX_SENSITIVITY), 7);
+        // anything above the maximum counts as the maximum.
+        assert_eq!(sensitivity_min_height_divisor(MAX_SENSITIVITY + 5), 108);
+    }
+
+    #[test]
+    fn zone_bands_round_on_odd_image_sizes() {
+        assert_eq!(zone_rect(MaskZone::Top, 0.25, 200, 101), Rect::new(0, 0, 200, 25));
+        assert_eq!(zone_rect(MaskZone::Bottom, 0.25, 200, 101), Rect::new(0, 76, 200, 25));
+        assert_eq!(zone_rect(MaskZone::Center, 0.25, 200, 101), Rect::new(75, 38, 50, 25));
+        assert_eq!(zone_rect(MaskZone::Left, 0.25, 101, 200), Rect::new(0, 0, 25, 200));
+        assert_eq!(zone_rect(MaskZone::Right, 0.25, 101, 200), Rect::new(76, 0, 25, 200));
+        // the bottom band reaches the last row.
+        let bottom = zone_rect(MaskZone::Bottom, 0.3, 200, 99);
+        assert_eq!(bottom.y + bottom.height, 99);
+    }
+
+    #[test]
+    fn regions_count_for_the_zone_their_centre_is_in() {
+        let top = zone_rect(MaskZone::Top, 0.25, 200, 101);
+        let bottom = zone_rect(MaskZone::Bottom, 0.25, 200, 101);
+        // centres at rows 24 and 25, either side of the top band's edge.
+        assert!(in_zone(&Rect::new(10, 19, 40, 10), &top));
+        assert!(!in_zone(&Rect::new(10, 20, 40, 10), &top));
+        // centres at rows 75 and 76, either side of the bottom band's edge.
+        assert!(!in_zone(&Rect::new(10, 70, 40, 10), &bottom));
+        assert!(in_zone(&Rect::new(10, 71, 40, 10), &bottom));
+        // a match in the bottom band is not masked with the top zone.
+        assert!(!in_zone(&Rect::new(10, 90, 40, 10), &top));
+    }
+}
@@ -1,190 +1,1788 @@
 use std::env;
-use std::path::PathBuf;
-use std::ffi::{CStr, CString, c_int};
-use thiserror::Error;
-use opencv::core::{in_range, Point, Rect, Scalar, Size};
+use std::fs;
+use std::ops::ControlFlow;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use opencv::core::{Rect, Scalar, Size};
+use opencv::imgcodecs::{imencode, imwrite};
+use opencv::imgproc::{CHAIN_APPROX_NONE, CHAIN_APPROX_SIMPLE, INTER_AREA, INTER_CUBIC, INTER_LINEAR, INTER_NEAREST, MORPH_CROSS, MORPH_ELLIPSE, MORPH_RECT};
+use opencv::photo::{INPAINT_NS, INPAINT_TELEA};
 use opencv::prelude::*;
-use opencv::imgcodecs::{imread, IMREAD_UNCHANGED, imwrite};
-use opencv::imgproc::{COLOR_BGR2HSV, cvt_color, dilate, MORPH_RECT, get_structuring_element, morphology_default_border_value, find_contours, RETR_EXTERNAL, bounding_rect, CHAIN_APPROX_NONE};
-use opencv::types::VectorOfVectorOfPoint;
-use tesseract_plumbing::{TessBaseApi, Text};
-
-#[derive(Debug, Error, PartialEq)]
-pub enum MaskMyNameError {
-    #[error("Read image failed.")]
-    ImageReadError(),
-    #[error("Mask text failed.")]
-    MaskTextError(),
-    #[error("Failed to initialize Tesseract.")]
-    TessInitError(),
-    #[error("Failed to get text from Tesseract.")]
-    TessGetTextError(),
-    #[error("Failed to create black bar Mat.")]
-    MaskingBarCreationError(),
-    #[error("No matching string found.")]
-    NoMatchingString(),
-}
-
-fn load_image(image_path: &PathBuf) -> Result<Mat, MaskMyNameError> {
-    match imread(image_path.to_str().expect("failed to convert PathBuf to str."), IMREAD_UNCHANGED) {
-        Ok(image) => {
-            Ok(image)
+use opencv::types::VectorOfu8;
+use mask_my_name::annotations::AnnotationFormat;
+use mask_my_name::mask_export::MaskEncoding;
+use mask_my_name::stamp::{Corner, Stamp};
+use unicode_script::Script;
+use mask_my_name::{animation, annotations, manifest, available_languages, color_profile, contact_sheet, format_rect, is_archive, is_stdin, is_url, check_coverage, difference, mask_layer, mask_my_name_with_coverage, value_histogram, mask_export, sensitivity_min_height_divisor, side_by_side, suspicions, redaction_log, segment, svg, tune,
+                   CodeMatch, ContrastCheck, Coverage, Detector, IMAGE_EXTENSIONS, MAX_SENSITIVITY, MaskConfig, MaskedImage, Timings, DEFAULT_CONFUSABLES, MaskMode, MaskMyNameError, MaskZone, MatchMode, OcrPreprocess, RegionMatch, SharpRegions, Suspicion, Tiling};
+
+/// Past this the minimum height is down to a pixel or two and the HSV bounds cover nearly everything.
+const MAX_RELAX_ATTEMPTS: u32 = 5;
+
+/// `--output` value that writes the masked image to standard output.
+const STDOUT_PATH: &str = "-";
+
+const USAGE: &str = "\
+usage: mask-my-name [OPTIONS] IMAGE_PATH YOUR_NAME(ALPHABET)
+       mask-my-name [--tessdata-dir DIR] list-langs
+       mask-my-name [OPTIONS] tune DIR LABELS YOUR_NAME [--sweep START:END:STEP]
+
+IMAGE_PATH is an image, a directory of images, an http(s) URL (--features url),
+a .zip archive of images (--features zip)
+or - to read one image from stdin. Reading from stdin needs --output PATH,
+or --output - to write the masked image to stdout as PNG; messages then go to stderr.
+
+Every option is described in the README, as are the MMN_ environment variables
+that set some of them.
+";
+
+/// `println!` for progress and reports, sent to stderr instead while the image itself goes to stdout.
+macro_rules! report {
+    ($args:expr, $($arg:tt)*) => {
+        match $args.output.as_deref() == Some(STDOUT_PATH) {
+            true => { eprintln!($($arg)*) },
+            false => { println!($($arg)*) }
+        }
+    };
+}
+
+enum Command {
+    Mask,
+    ListLangs,
+    Help,
+    /// Score the settings against the labelled images of `image_path`, optionally for every fuzzy threshold in `sweep`.
+    Tune { labels: PathBuf, sweep: Vec<f64> },
+}
+
+struct Cli {
+    command: Command,
+    /// Empty for commands that take no input image.
+    image_path: PathBuf,
+    targets: Vec<String>,
+    /// Targets of the images `--manifest` names, used instead of `targets`.
+    manifest: Option<manifest::Manifest>,
+    keep_going: bool,
+    /// Print the value channel histogram instead of masking.
+    histogram: bool,
+    output_template: String,
+    /// The one output file, instead of `output_template`. `STDOUT_PATH` for stdout.
+    output: Option<String>,
+    /// Refuse to overwrite existing output files.
+    no_clobber: bool,
+    /// Write images without a match too, as they were.
+    always_write: bool,
+    /// No warnings about likely incomplete detection.
+    quiet: bool,
+    /// List the unique matched texts instead of the per-region report.
+    print_matches: bool,
+    /// Print only the output path on success, and the detected regions with their text when nothing matched.
+    diagnose_on_failure: bool,
+    /// Print the time spent in each stage, and the averages at the end of a batch.
+    timings: bool,
+    /// Inputs finished in an earlier run are listed here and skipped; each one finished now is appended.
+    resume: Option<PathBuf>,
+    /// Append a row per matched region to this CSV file.
+    csv_log: Option<PathBuf>,
+    /// SQLite database recording the same as `csv_log`.
+    db_log: Option<PathBuf>,
+    /// Append a row per OCR'd region, matched or not, to this CSV file.
+    ocr_dump: Option<PathBuf>,
+    /// Template for the transparent PNG holding only the masks.
+    mask_layer: Option<String>,
+    /// Tag the masked output as sRGB, or with the input's ICC profile.
+    srgb: bool,
+    /// Template for the SVG overlay of the detected regions.
+    svg_overlay: Option<String>,
+    segmentation: Option<String>,
+    segmentation_format: MaskEncoding,
+    compare: Option<String>,
+    diff: Option<String>,
+    /// Binary `--diff` of the pixels changed by more than this.
+    diff_threshold: Option<u8>,
+    annotations: Option<String>,
+    annotation_format: AnnotationFormat,
+    /// Tile every masked image into this one file instead of writing them separately.
+    contact_sheet: Option<String>,
+    /// Time a sample of the batch and print the extrapolated total instead of writing anything.
+    estimate: bool,
+    /// When `--deadline` stops the run, set from the time of parsing.
+    deadline: Option<Instant>,
+    columns: usize,
+    tile_size: Size,
+    config: MaskConfig,
+}
+
+/// Prefix of the environment variables read as settings, see `ENV_FLAGS`.
+const ENV_PREFIX: &str = "MMN_";
+
+/// `(variable, flag, takes a value)`. Variables of switches take `1`/`true`/`yes` or `0`/`false`/`no`.
+const ENV_FLAGS: [(&str, &str, bool); 22] = [
+    ("MMN_LANG", "--lang", true),
+    ("MMN_TESSDATA_DIR", "--tessdata-dir", true),
+    ("MMN_MASK_MODE", "--mask-mode", true),
+    ("MMN_DETECTOR", "--detector", true),
+    ("MMN_EAST_MODEL", "--east-model", true),
+    ("MMN_OUTPUT_TEMPLATE", "--output-template", true),
+    ("MMN_FUZZY", "--fuzzy", true),
+    ("MMN_MIN_HEIGHT", "--min-height", true),
+    ("MMN_OCR_MARGIN", "--ocr-margin", true),
+    ("MMN_TIMEOUT", "--timeout", true),
+    ("MMN_DEADLINE", "--deadline", true),
+    ("MMN_SEED", "--seed", true),
+    ("MMN_CSV", "--csv", true),
+    ("MMN_DB", "--db", true),
+    ("MMN_OCR_DUMP", "--ocr-dump", true),
+    ("MMN_STAMP", "--stamp", true),
+    ("MMN_TARGET", "--target", true),
+    ("MMN_KEEP_GOING", "--keep-going", false),
+    ("MMN_NO_CLOBBER", "--no-clobber", false),
+    ("MMN_AUTO_ORIENT", "--auto-orient", false),
+    ("MMN_REQUIRE_ALL", "--require-all", false),
+    ("MMN_CONFUSABLES", "--confusables", false),
+];
+
+/// The settings of `ENV_FLAGS` variables that are set, as flags to parse ahead of the command line,
+/// so flags given there override them. Unknown `MMN_` variables are reported and ignored.
+fn env_args() -> Result<Vec<String>, MaskMyNameError> {
+    let mut flags = Vec::new();
+    for (variable, flag, takes_value) in ENV_FLAGS {
+        let value = match env::var(variable) {
+            Ok(value) => { value },
+            Err(env::VarError::NotPresent) => { continue; },
+            Err(env::VarError::NotUnicode(_)) => { return Err(MaskMyNameError::InvalidArgument(format!("{} is not valid UTF-8", variable))); }
+        };
+        if takes_value {
+            flags.extend([flag.to_string(), value]);
+            continue;
+        }
+        match value.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" => { flags.push(flag.to_string()); },
+            "0" | "false" | "no" | "" => {},
+            _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects 1 or 0 but got {}", variable, value))); }
+        }
+    }
+    for (variable, _) in env::vars_os() {
+        if let Some(name) = variable.to_str().filter(|name| name.starts_with(ENV_PREFIX)) {
+            if !ENV_FLAGS.iter().any(|(known, _, _)| *known == name) {
+                eprintln!("Ignoring unknown environment variable {}.", name);
+            }
+        }
+    }
+    Ok(flags)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, MaskMyNameError> {
+    args.next().ok_or(MaskMyNameError::InvalidArgument(format!("{} requires a value", flag)))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, MaskMyNameError> {
+    value.parse().map_err(|_| MaskMyNameError::InvalidArgument(format!("{} expects a number but got {}", flag, value)))
+}
+
+/// A finite number above zero. NaN would fail every comparison made with it and silently disable what it sets.
+fn parse_positive(flag: &str, value: &str) -> Result<f64, MaskMyNameError> {
+    let number: f64 = parse_number(flag, value)?;
+    if !number.is_finite() || number <= 0. {
+        return Err(MaskMyNameError::InvalidArgument(format!("{} must be positive but got {}", flag, value)));
+    }
+    Ok(number)
+}
+
+/// The instant `value` seconds from now, refusing what `Duration` or `Instant` cannot hold, like `inf` or `1e300`.
+fn parse_deadline(flag: &str, value: &str) -> Result<Instant, MaskMyNameError> {
+    let too_long = || MaskMyNameError::InvalidArgument(format!("{} of {} seconds is too long", flag, value));
+    let seconds = Duration::try_from_secs_f64(parse_positive(flag, value)?).map_err(|_| too_long())?;
+    Instant::now().checked_add(seconds).ok_or_else(too_long)
+}
+
+fn parse_rect(flag: &str, value: &str) -> Result<Rect, MaskMyNameError> {
+    let parts: Vec<i32> = value.split(',').map(|p| p.trim().parse::<i32>()).collect::<Result<_, _>>()
+        .map_err(|_| MaskMyNameError::InvalidArgument(format!("{} expects x,y,w,h but got {}", flag, value)))?;
+    match parts[..] {
+        [x, y, w, h] if w > 0 && h > 0 => { Ok(Rect::new(x, y, w, h)) },
+        _ => { Err(MaskMyNameError::InvalidArgument(format!("{} expects x,y,w,h but got {}", flag, value))) }
+    }
+}
+
+fn parse_size(flag: &str, value: &str) -> Result<Size, MaskMyNameError> {
+    match value.split_once('x').map(|(w, h)| (w.trim().parse::<i32>(), h.trim().parse::<i32>())) {
+        Some((Ok(width), Ok(height))) if width > 0 && height > 0 => { Ok(Size::new(width, height)) },
+        _ => { Err(MaskMyNameError::InvalidArgument(format!("{} expects WxH but got {}", flag, value))) }
+    }
+}
+
+/// The mask mode named by `value`, for `--mask-mode` and `--target-mask`.
+fn parse_mask_mode(value: &str) -> Result<MaskMode, MaskMyNameError> {
+    match value {
+        "bar" => { Ok(MaskMode::Bar) },
+        "auto-color" => { Ok(MaskMode::AutoColor) },
+        "blur" => { Ok(MaskMode::Blur) },
+        "pixelate" => { Ok(MaskMode::Pixelate) },
+        "strong" => { Ok(MaskMode::Strong) },
+        "noise" => { Ok(MaskMode::Noise) },
+        "inpaint" => { Ok(MaskMode::Inpaint) },
+        _ => { Err(MaskMyNameError::InvalidArgument(format!("unknown mask mode {}", value))) }
+    }
+}
+
+/// The `CHAIN_APPROX_*` contour approximation named by `value`.
+fn parse_contour_approx(flag: &str, value: &str) -> Result<i32, MaskMyNameError> {
+    match value {
+        "simple" => { Ok(CHAIN_APPROX_SIMPLE) },
+        "none" => { Ok(CHAIN_APPROX_NONE) },
+        _ => { Err(MaskMyNameError::InvalidArgument(format!("{} expects simple or none but got {}", flag, value))) }
+    }
+}
+
+/// The `MORPH_*` structuring element shape named by `value`.
+fn parse_kernel_shape(flag: &str, value: &str) -> Result<i32, MaskMyNameError> {
+    match value {
+        "rect" => { Ok(MORPH_RECT) },
+        "ellipse" => { Ok(MORPH_ELLIPSE) },
+        "cross" => { Ok(MORPH_CROSS) },
+        _ => { Err(MaskMyNameError::InvalidArgument(format!("{} expects rect, ellipse or cross but got {}", flag, value))) }
+    }
+}
+
+/// A Unicode script by its full or four-letter name in any case, e.g. `han`, `Hani` or `old-italic`.
+fn parse_script(name: &str) -> Result<Script, MaskMyNameError> {
+    let title: Vec<String> = name.split(['_', '-', ' ']).map(|word| {
+        let mut chars = word.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect()).unwrap_or_default()
+    }).collect();
+    let title = title.join("_");
+    Script::from_full_name(&title).or(Script::from_short_name(&title))
+        .ok_or(MaskMyNameError::InvalidArgument(format!("--mask-script expects Unicode script names such as latin, han, hiragana or katakana but got {}", name)))
+}
+
+fn parse_hsv(flag: &str, value: &str) -> Result<Scalar, MaskMyNameError> {
+    let invalid = || MaskMyNameError::InvalidArgument(format!("{} expects H,S,V with H in 0-180 and S, V in 0-255 but got {}", flag, value));
+    let parts: Vec<f64> = value.split(',').map(|p| p.trim().parse::<f64>()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    match parts[..] {
+        [h, s, v] if (0. ..=180.).contains(&h) && (0. ..=255.).contains(&s) && (0. ..=255.).contains(&v) => {
+            Ok(Scalar::new(h, s, v, 0.))
         },
-        Err(_) => {
-            Err(MaskMyNameError::ImageReadError())
+        _ => { Err(invalid()) }
+    }
+}
+
+fn box_from_json(value: &serde_json::Value) -> Option<Rect> {
+    let field = |names: [&str; 2]| names.iter().find_map(|name| value.get(name)).and_then(|v| v.as_i64()).map(|v| v as i32);
+    let [x, y, w, h] = match value.as_array() {
+        Some(parts) if parts.len() == 4 => { [0, 1, 2, 3].map(|i| parts[i].as_i64().map(|v| v as i32)) },
+        _ => { [field(["x", "x"]), field(["y", "y"]), field(["width", "w"]), field(["height", "h"])] }
+    };
+    Some(Rect::new(x?, y?, w?, h?)).filter(|rect| rect.width > 0 && rect.height > 0)
+}
+
+/// Boxes from a `.json` file, an array of `[x, y, w, h]` or `{"x", "y", "width", "height"}` (`w`/`h` also accepted),
+/// or else CSV lines of `x,y,w,h`, a header line being skipped.
+fn load_boxes(flag: &str, path: &str) -> Result<Vec<Rect>, MaskMyNameError> {
+    let invalid = |detail: String| MaskMyNameError::InvalidArgument(format!("{} {}: {}", flag, path, detail));
+    let contents = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    if path.to_lowercase().ends_with(".json") {
+        let value: serde_json::Value = serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        return value.as_array().ok_or(invalid("expected an array of boxes".to_string()))?.iter()
+            .map(|entry| box_from_json(entry).ok_or(invalid(format!("invalid box {}", entry))))
+            .collect();
+    }
+    let mut boxes = Vec::new();
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).trim(csv::Trim::All).from_reader(contents.as_bytes());
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| invalid(e.to_string()))?;
+        let line = record.iter().collect::<Vec<_>>().join(",");
+        match parse_rect(flag, &line) {
+            Ok(rect) => { boxes.push(rect); },
+            // a header such as x,y,w,h.
+            Err(_) if index == 0 && record.iter().all(|field| field.parse::<i32>().is_err()) => {},
+            Err(_) => { return Err(invalid(format!("line {} is not x,y,w,h", index + 1))); }
         }
     }
+    Ok(boxes)
 }
 
-fn iterations(frame_height: i32) -> i32 {
-    if frame_height >= 720 {
-        5
-    } else {
-        3
+fn parse_detector(name: &str, east_model: Option<&Path>, config: &MaskConfig) -> Result<Detector, MaskMyNameError> {
+    Ok(match name {
+        "contour" => Detector::Contour,
+        "mser" => Detector::Mser,
+        "dnn" => match east_model {
+            Some(model_path) if model_path.is_file() => Detector::Dnn(model_path.to_path_buf()),
+            Some(model_path) => {
+                return Err(MaskMyNameError::InvalidArgument(format!("EAST model {} not found", model_path.display())));
+            }
+            None => {
+                return Err(MaskMyNameError::InvalidArgument("--detector dnn requires --east-model".to_string()));
+            }
+        },
+        "color-range" => {
+            if config.hsv_ranges.is_empty() && (config.hsv_lower.is_none() || config.hsv_upper.is_none()) {
+                return Err(MaskMyNameError::InvalidArgument("--mask-color-range requires --hsv-lower and --hsv-upper, or --hsv-range".to_string()));
+            }
+            Detector::ColorRange
+        },
+        other => {
+            return Err(MaskMyNameError::InvalidArgument(format!("unknown detector {}", other)));
+        }
+    })
+}
+
+/// With a `manifest`, images it lists bring their own targets, so none need to be given.
+fn check_targets(targets: &[String], config: &MaskConfig, manifest: bool) -> Result<(), MaskMyNameError> {
+    if config.masks_everything() {
+        return match config.invert_mask {
+            true => { Err(MaskMyNameError::InvalidArgument("--invert-mask with --redact-all or --mask-color-range would mask nothing".to_string())) },
+            false => { Ok(()) }
+        };
+    }
+    if targets.is_empty() && !manifest && config.lang_targets.is_empty() && config.match_script.is_none() && config.mask_scripts.is_empty() {
+        return Err(MaskMyNameError::InvalidArgument("no target given, pass a name, --match-script, --mask-script or --redact-all".to_string()));
     }
+    match targets.iter().any(|t| t.trim().is_empty()) {
+        true => { Err(MaskMyNameError::EmptyTarget()) },
+        false => { Ok(()) }
+    }
+}
+
+fn parse_args() -> Result<Cli, MaskMyNameError> {
+    parse_arg_list(env_args()?.into_iter().chain(env::args().skip(1)))
 }
 
-fn max_range(frame_height: i32) -> f64 {
-    if frame_height >= 720 {
-        30.
+/// Parses `args`, the `env_args` followed by the command line without the program name.
+fn parse_arg_list(mut args: impl Iterator<Item = String>) -> Result<Cli, MaskMyNameError> {
+    let mut positional = Vec::new();
+    let mut keep_going = false;
+    let mut histogram = false;
+    let mut output_template = DEFAULT_OUTPUT_TEMPLATE.to_string();
+    let mut output = None;
+    let mut help = false;
+    let mut contact_sheet = None;
+    let mut estimate = false;
+    let mut deadline = None;
+    let mut mask_layer = None;
+    let mut svg_overlay = None;
+    let mut segmentation = None;
+    let mut segmentation_format = MaskEncoding::Rle;
+    let mut compare = None;
+    let mut diff = None;
+    let mut diff_threshold = None;
+    let mut annotations = None;
+    let mut annotation_format = AnnotationFormat::LabelMe;
+    let mut srgb = false;
+    let mut no_clobber = false;
+    let mut always_write = false;
+    let mut quiet = false;
+    let mut divisor_given = false;
+    let mut print_matches = false;
+    let mut timings = false;
+    let mut diagnose_on_failure = false;
+    let mut resume = None;
+    let mut manifest_path = None;
+    let mut csv_log = None;
+    let mut db_log = None;
+    let mut ocr_dump = None;
+    let mut columns = 4;
+    let mut tile_size = Size::new(320, 240);
+    let mut config = MaskConfig::default();
+    // unset means contour, kept apart from an explicit --detector contour to refuse it next to --boxes.
+    let mut detector: Option<String> = None;
+    let mut east_model: Option<PathBuf> = None;
+    let mut boxes: Option<Vec<Rect>> = None;
+    let mut targets = Vec::new();
+    let mut tile_overlap = 64;
+    let mut sweep = Vec::new();
+    let mut stamp_text: Option<String> = None;
+    let mut stamp = Stamp::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keep-going" => { keep_going = true; }
+            "--histogram" => { histogram = true; }
+            "--output-template" => {
+                output_template = next_value(&mut args, &arg)?;
+                parse_template(&output_template)?;
+            }
+            "--output" => { output = Some(next_value(&mut args, &arg)?); }
+            "--help" | "-h" => { help = true; }
+            "--no-clobber" => { no_clobber = true; }
+            "--always-write" => { always_write = true; }
+            "--quiet" => { quiet = true; }
+            "--print-matches" => { print_matches = true; }
+            "--timings" => { timings = true; }
+            "--diagnose-on-failure" => { diagnose_on_failure = true; }
+            "--resume" => { resume = Some(PathBuf::from(next_value(&mut args, &arg)?)); }
+            "--manifest" => { manifest_path = Some(PathBuf::from(next_value(&mut args, &arg)?)); }
+            "--csv" => { csv_log = Some(PathBuf::from(next_value(&mut args, &arg)?)); }
+            "--db" => {
+                let path = PathBuf::from(next_value(&mut args, &arg)?);
+                if cfg!(not(feature = "sqlite")) {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} needs a build with --features sqlite", arg)));
+                }
+                db_log = Some(path);
+            }
+            "--ocr-dump" => { ocr_dump = Some(PathBuf::from(next_value(&mut args, &arg)?)); }
+            "--force" => { no_clobber = false; }
+            "--mask-layer" => {
+                let template = next_value(&mut args, &arg)?;
+                parse_template(&template)?;
+                mask_layer = Some(template);
+            }
+            "--srgb" => { srgb = true; }
+            "--svg" => {
+                let template = next_value(&mut args, &arg)?;
+                parse_template(&template)?;
+                svg_overlay = Some(template);
+            }
+            "--segmentation" => {
+                let template = next_value(&mut args, &arg)?;
+                parse_template(&template)?;
+                segmentation = Some(template);
+            }
+            "--compare" => {
+                let template = next_value(&mut args, &arg)?;
+                parse_template(&template)?;
+                config.keep_original = true;
+                compare = Some(template);
+            }
+            "--diff" => {
+                let template = next_value(&mut args, &arg)?;
+                parse_template(&template)?;
+                config.keep_original = true;
+                diff = Some(template);
+            }
+            "--diff-threshold" => {
+                diff_threshold = Some(parse_number(&arg, &next_value(&mut args, &arg)?)?);
+            }
+            "--annotations" => {
+                let template = next_value(&mut args, &arg)?;
+                parse_template(&template)?;
+                annotations = Some(template);
+            }
+            "--annotation-format" => {
+                let value = next_value(&mut args, &arg)?;
+                annotation_format = match value.as_str() {
+                    "labelme" => AnnotationFormat::LabelMe,
+                    "coco" => AnnotationFormat::Coco,
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects labelme or coco but got {}", arg, value))); }
+                };
+            }
+            "--segmentation-format" => {
+                let value = next_value(&mut args, &arg)?;
+                segmentation_format = match value.as_str() {
+                    "rle" => MaskEncoding::Rle,
+                    "polygons" => MaskEncoding::Polygons,
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects rle or polygons but got {}", arg, value))); }
+                };
+            }
+            "--contact-sheet" => { contact_sheet = Some(next_value(&mut args, &arg)?); }
+            "--estimate" => { estimate = true; }
+            "--deadline" => { deadline = Some(parse_deadline(&arg, &next_value(&mut args, &arg)?)?); }
+            "--columns" => {
+                columns = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if columns == 0 {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} must be positive", arg)));
+                }
+            }
+            "--tile-size" => { tile_size = parse_size(&arg, &next_value(&mut args, &arg)?)?; }
+            "--tiled" => {
+                let size = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                config.tiling = Some(Tiling { size, overlap: 0 });
+            }
+            "--tile-overlap" => { tile_overlap = parse_number(&arg, &next_value(&mut args, &arg)?)?; }
+            "--detector" | "--detectors" => { detector = Some(next_value(&mut args, &arg)?); }
+            "--boxes" => {
+                let path = next_value(&mut args, &arg)?;
+                boxes = Some(load_boxes(&arg, &path)?);
+            }
+            "--east-model" => { east_model = Some(PathBuf::from(next_value(&mut args, &arg)?)); }
+            "--auto-orient" => { config.auto_orient = true; }
+            "--force-8bit" => { config.force_8bit = true; }
+            "--lang" => { config.lang = next_value(&mut args, &arg)?; }
+            "--tess-var" => {
+                let value = next_value(&mut args, &arg)?;
+                match value.split_once('=') {
+                    Some((name, setting)) if !name.trim().is_empty() => {
+                        config.tess_vars.push((name.trim().to_string(), setting.to_string()));
+                    },
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects key=value but got {}", arg, value))); }
+                }
+            }
+            "--tessdata-dir" => { config.tessdata_dir = Some(PathBuf::from(next_value(&mut args, &arg)?)); }
+            "--ocr-retries" => { config.ocr_retries = parse_number(&arg, &next_value(&mut args, &arg)?)?; }
+            "--min-height-divisor" => {
+                divisor_given = true;
+                config.min_height_divisor = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if config.min_height_divisor <= 0 {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} must be positive", arg)));
+                }
+            }
+            "--sensitivity" => {
+                config.sensitivity = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if config.sensitivity > MAX_SENSITIVITY {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} expects 0 to {}", arg, MAX_SENSITIVITY)));
+                }
+            }
+            "--min-height" => { config.min_height = Some(parse_number(&arg, &next_value(&mut args, &arg)?)?); }
+            "--max-aspect-ratio" => { config.max_aspect_ratio = parse_positive(&arg, &next_value(&mut args, &arg)?)?; }
+            "--min-region-area" => { config.min_region_area = Some(parse_number(&arg, &next_value(&mut args, &arg)?)?); }
+            "--max-region-area" => { config.max_region_area = Some(parse_number(&arg, &next_value(&mut args, &arg)?)?); }
+            "--hsv-lower" => { config.hsv_lower = Some(parse_hsv(&arg, &next_value(&mut args, &arg)?)?); }
+            "--hsv-upper" => { config.hsv_upper = Some(parse_hsv(&arg, &next_value(&mut args, &arg)?)?); }
+            "--hsv-range" => {
+                let value = next_value(&mut args, &arg)?;
+                match value.split_once(':') {
+                    Some((lower, upper)) => { config.hsv_ranges.push((parse_hsv(&arg, lower)?, parse_hsv(&arg, upper)?)); },
+                    None => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects H,S,V:H,S,V but got {}", arg, value))); }
+                }
+            }
+            "--mask-color-range" => { detector = Some("color-range".to_string()); }
+            "--auto-relax" => {
+                config.relax_attempts = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if !(1..=MAX_RELAX_ATTEMPTS).contains(&config.relax_attempts) {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} expects 1 to {} attempts", arg, MAX_RELAX_ATTEMPTS)));
+                }
+            }
+            "--contour-approx" => { config.contour_approx = parse_contour_approx(&arg, &next_value(&mut args, &arg)?)?; }
+            "--kernel-shape" => { config.kernel_shape = parse_kernel_shape(&arg, &next_value(&mut args, &arg)?)?; }
+            "--close" => { config.close_kernel = Some(parse_size(&arg, &next_value(&mut args, &arg)?)?); }
+            "--auto-color" => { config.mask_mode = MaskMode::AutoColor; }
+            "--check-contrast" => { config.contrast_check = Some(ContrastCheck::Warn); }
+            "--auto-contrast" => { config.contrast_check = Some(ContrastCheck::Switch); }
+            "--stamp" => {
+                let text = next_value(&mut args, &arg)?;
+                if text.trim().is_empty() {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} expects a text", arg)));
+                }
+                stamp_text = Some(text);
+            }
+            "--stamp-position" => {
+                let value = next_value(&mut args, &arg)?;
+                stamp.corner = match value.as_str() {
+                    "top-left" => Corner::TopLeft,
+                    "top-right" => Corner::TopRight,
+                    "bottom-left" => Corner::BottomLeft,
+                    "bottom-right" => Corner::BottomRight,
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects top-left, top-right, bottom-left or bottom-right but got {}", arg, value))); }
+                };
+            }
+            "--stamp-color" => {
+                let value = next_value(&mut args, &arg)?;
+                let parts: Vec<u8> = value.split(',').map(|p| p.trim().parse::<u8>()).collect::<Result<_, _>>().unwrap_or_default();
+                stamp.color = match parts[..] {
+                    [r, g, b] => [r, g, b],
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects R,G,B in 0-255 but got {}", arg, value))); }
+                };
+            }
+            "--stamp-scale" => { stamp.scale = parse_positive(&arg, &next_value(&mut args, &arg)?)?; }
+            "--mask-mode" => { config.mask_mode = parse_mask_mode(&next_value(&mut args, &arg)?)?; }
+            "--target-mask" => {
+                let value = next_value(&mut args, &arg)?;
+                match value.split_once(':') {
+                    Some((mode, name)) if !name.is_empty() => {
+                        config.target_mask_modes.push((name.to_string(), parse_mask_mode(mode)?));
+                    },
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects MODE:NAME but got {}", arg, value))); }
+                }
+            }
+            "--ocr-margin" => {
+                config.ocr_margin = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if config.ocr_margin < 0 {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} must not be negative", arg)));
+                }
+            }
+            "--rescan-below" => {
+                let value = next_value(&mut args, &arg)?;
+                match value.parse::<i32>() {
+                    Ok(confidence) if (0..=100).contains(&confidence) => { config.rescan_below = Some(confidence); },
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects a confidence between 0 and 100 but got {}", arg, value))); }
+                }
+            }
+            "--hull" => { config.hull = true; }
+            "--min-contrast" => {
+                let min: f64 = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if !(0. ..=255.).contains(&min) {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} expects a standard deviation between 0 and 255", arg)));
+                }
+                config.min_region_contrast = Some(min);
+            }
+            "--ocr-preprocess" => {
+                let value = next_value(&mut args, &arg)?;
+                config.ocr_preprocess = match value.as_str() {
+                    "none" => None,
+                    "grayscale" => Some(OcrPreprocess::Grayscale),
+                    "otsu" => Some(OcrPreprocess::Otsu),
+                    "stretch" => Some(OcrPreprocess::Stretch),
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects none, grayscale, otsu or stretch but got {}", arg, value))); }
+                };
+            }
+            "--rounded" => { config.corner_radius = Some(parse_number(&arg, &next_value(&mut args, &arg)?)?); }
+            "--bar-scale" => { config.bar_scale = parse_positive(&arg, &next_value(&mut args, &arg)?)?; }
+            "--pixel-size" => {
+                config.pixel_size = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if config.pixel_size <= 0 {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} must be positive", arg)));
+                }
+            }
+            "--inpaint-method" => {
+                let value = next_value(&mut args, &arg)?;
+                config.inpaint_method = match value.as_str() {
+                    "telea" => INPAINT_TELEA,
+                    "ns" => INPAINT_NS,
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects telea or ns but got {}", arg, value))); }
+                };
+            }
+            "--interpolation" => {
+                let value = next_value(&mut args, &arg)?;
+                config.interpolation = Some(match value.as_str() {
+                    "area" => INTER_AREA,
+                    "linear" => INTER_LINEAR,
+                    "cubic" => INTER_CUBIC,
+                    "nearest" => INTER_NEAREST,
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects area, linear, cubic or nearest but got {}", arg, value))); }
+                });
+            }
+            "--blur-strength" => {
+                config.blur_strength = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if config.blur_strength <= 0 || config.blur_strength % 2 == 0 {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} must be a positive odd number", arg)));
+                }
+            }
+            "--blur-background" => {
+                let value = next_value(&mut args, &arg)?;
+                config.blur_background = match value.as_str() {
+                    "matched" => Some(SharpRegions::Matched),
+                    "detected" => Some(SharpRegions::Detected),
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects matched or detected but got {}", arg, value))); }
+                };
+            }
+            "--crops-dir" => { config.crops_dir = Some(PathBuf::from(next_value(&mut args, &arg)?)); }
+            "--crops-matched-only" => { config.crops_matched_only = true; }
+            "--numeric" => { config.match_mode = MatchMode::Numeric; }
+            "--exact" => { config.match_mode = MatchMode::Exact; }
+            "--prefix" => { config.match_mode = MatchMode::Prefix; }
+            "--suffix" => { config.match_mode = MatchMode::Suffix; }
+            "--phonetic" => { config.match_mode = MatchMode::Phonetic; }
+            "--glob" => { config.match_mode = MatchMode::Glob; }
+            "--fuzzy" => {
+                let value = next_value(&mut args, &arg)?;
+                match value.parse::<f64>() {
+                    Ok(threshold) if (0. ..=1.).contains(&threshold) => { config.match_mode = MatchMode::Fuzzy(threshold); },
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects a threshold between 0 and 1 but got {}", arg, value))); }
+                }
+            }
+            "--segment-words" => { config.segment_words = true; }
+            "--strip-digits" => { config.strip_digits = true; }
+            "--wordlist" => { config.wordlist = segment::load_wordlist(Path::new(&next_value(&mut args, &arg)?))?; }
+            "--confusables" => { config.confusables = DEFAULT_CONFUSABLES.iter().map(|g| g.to_string()).collect(); }
+            "--confusable-map" => {
+                let value = next_value(&mut args, &arg)?;
+                config.confusables = value.split(',').map(|g| g.trim().to_lowercase()).filter(|g| !g.is_empty()).collect();
+                if config.confusables.iter().any(|g| g.chars().count() < 2) {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} expects comma separated groups of two or more characters but got {}", arg, value)));
+                }
+            }
+            "--match-script" => {
+                let path = next_value(&mut args, &arg)?;
+                config.match_script = Some(fs::read_to_string(&path)
+                    .map_err(|_| MaskMyNameError::InvalidArgument(format!("{} could not read {}", arg, path)))?);
+            }
+            "--never-mask" => { config.never_mask.push(next_value(&mut args, &arg)?); }
+            "--never-mask-file" => {
+                config.never_mask.extend(segment::load_wordlist(Path::new(&next_value(&mut args, &arg)?))?);
+            }
+            "--min-token-length" => { config.min_token_length = parse_number(&arg, &next_value(&mut args, &arg)?)?; }
+            "--lang-target" => {
+                let value = next_value(&mut args, &arg)?;
+                match value.split_once(':') {
+                    Some((lang, name)) if !lang.is_empty() && !name.is_empty() => {
+                        config.lang_targets.push((lang.to_string(), name.to_string()));
+                    },
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects LANG:NAME but got {}", arg, value))); }
+                }
+            }
+            "--target" => { targets.push(next_value(&mut args, &arg)?); }
+            "--mask-script" => {
+                for name in next_value(&mut args, &arg)?.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                    config.mask_scripts.push(parse_script(name)?);
+                }
+            }
+            "--sweep" => {
+                let value = next_value(&mut args, &arg)?;
+                let parts: Vec<f64> = value.split(':').map(|p| p.trim().parse::<f64>()).collect::<Result<_, _>>().unwrap_or_default();
+                match parts[..] {
+                    [start, end, step] if step > 0. && (0. ..=1.).contains(&start) && (start..=1.).contains(&end) => {
+                        sweep = (0..).map(|i| start + i as f64 * step).take_while(|t| *t <= end + 1e-9).collect();
+                    },
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects START:END:STEP thresholds between 0 and 1 but got {}", arg, value))); }
+                }
+            }
+            "--target-env" => {
+                let name = next_value(&mut args, &arg)?;
+                targets.push(env::var(&name).map_err(|_| MaskMyNameError::InvalidArgument(format!("{} {} is not set or not valid UTF-8", arg, name)))?);
+            }
+            "--invert-mask" => { config.invert_mask = true; }
+            "--require-all" => { config.require_all = true; }
+            "--redact-all" => { config.redact_all = true; }
+            "--max-matches-per-image" => {
+                let max = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if max == 0 {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} must be positive", arg)));
+                }
+                config.max_matches = Some(max);
+            }
+            "--mask-qr" => { config.mask_qr = true; }
+            "--verify" => { config.verify = true; }
+            "--interactive" => { config.interactive = true; }
+            "--reuse-similar-frames" => { config.reuse_similar_frames = true; }
+            "--hold-frames" => { config.hold_frames = parse_number(&arg, &next_value(&mut args, &arg)?)?; }
+            "--timeout" => { config.fetch_timeout = Duration::from_secs(parse_number(&arg, &next_value(&mut args, &arg)?)?); }
+            "--seed" => { config.seed = Some(parse_number(&arg, &next_value(&mut args, &arg)?)?); }
+            "--mask-zone" => {
+                let value = next_value(&mut args, &arg)?;
+                config.mask_zone = Some(match value.as_str() {
+                    "top" => MaskZone::Top,
+                    "bottom" => MaskZone::Bottom,
+                    "left" => MaskZone::Left,
+                    "right" => MaskZone::Right,
+                    "center" => MaskZone::Center,
+                    _ => { return Err(MaskMyNameError::InvalidArgument(format!("{} expects top, bottom, left, right or center but got {}", arg, value))); }
+                });
+            }
+            "--mask-zone-fraction" => {
+                config.mask_zone_fraction = parse_number(&arg, &next_value(&mut args, &arg)?)?;
+                if !(config.mask_zone_fraction > 0. && config.mask_zone_fraction <= 1.) {
+                    return Err(MaskMyNameError::InvalidArgument(format!("{} expects a fraction above 0 and up to 1", arg)));
+                }
+            }
+            "--crop" => { config.crop = Some(parse_rect(&arg, &next_value(&mut args, &arg)?)?); }
+            "--include" => { config.include_zones.push(parse_rect(&arg, &next_value(&mut args, &arg)?)?); }
+            flag if flag.starts_with("--") => {
+                return Err(MaskMyNameError::InvalidArgument(format!("unknown option {}", flag)));
+            }
+            _ => { positional.push(arg); }
+        }
+    }
+    if help || positional.first().map(|p| p == "list-langs").unwrap_or(false) {
+        return Ok(Cli {
+            command: if help { Command::Help } else { Command::ListLangs },
+            image_path: PathBuf::new(),
+            targets,
+            manifest: None,
+            keep_going,
+            histogram,
+            output_template,
+            output,
+            no_clobber,
+            always_write,
+            quiet,
+            print_matches,
+            timings,
+            diagnose_on_failure,
+            resume,
+            csv_log,
+            db_log,
+            ocr_dump,
+            mask_layer,
+            svg_overlay,
+            segmentation,
+            segmentation_format,
+            compare,
+            diff,
+            diff_threshold,
+            annotations,
+            annotation_format,
+            srgb,
+            contact_sheet,
+            estimate,
+            deadline,
+            columns,
+            tile_size,
+            config,
+        });
+    }
+    let mut positional = positional.into_iter().peekable();
+    let tune = positional.next_if(|p| p == "tune").is_some();
+    let path = positional.next().ok_or(MaskMyNameError::InvalidArgument("no path given".to_string()))?;
+    let command = match tune {
+        true => {
+            let labels = positional.next().ok_or(MaskMyNameError::InvalidArgument("tune expects a directory and a labels file".to_string()))?;
+            Command::Tune { labels: PathBuf::from(labels), sweep }
+        },
+        false if !sweep.is_empty() => { return Err(MaskMyNameError::InvalidArgument("--sweep only applies to tune".to_string())); },
+        false => { Command::Mask }
+    };
+    if let Some(target) = positional.next() {
+        targets.insert(0, target);
+    }
+    let manifest = match manifest_path {
+        Some(_) if is_url(Path::new(&path)) || is_stdin(Path::new(&path)) || is_archive(Path::new(&path)) || tune => {
+            return Err(MaskMyNameError::InvalidArgument("--manifest only applies to image files and directories".to_string()));
+        },
+        // the file names are relative to the input directory, or that of the input file.
+        Some(manifest_path) => {
+            let input = Path::new(&path);
+            let base = if input.is_dir() { input } else { input.parent().unwrap_or(Path::new("")) };
+            Some(manifest::load_manifest(&manifest_path, base)?)
+        },
+        None => { None }
+    };
+    match stamp_text {
+        Some(text) => { config.stamp = Some(Stamp { text, ..stamp }); },
+        None if stamp != Stamp::default() => {
+            return Err(MaskMyNameError::InvalidArgument("--stamp-position, --stamp-color and --stamp-scale only apply with --stamp".to_string()));
+        },
+        None => {}
+    }
+    if let (MatchMode::Glob, Some(target)) = (config.match_mode, targets.iter().find(|t| !t.is_empty() && t.chars().all(|c| c == '*' || c == '?'))) {
+        return Err(MaskMyNameError::InvalidArgument(format!("--glob pattern {} has nothing but wildcards and would mask every region", target)));
+    }
+    // an explicit divisor wins, like every flag the sensitivity stands for.
+    if !divisor_given {
+        config.min_height_divisor = sensitivity_min_height_divisor(config.sensitivity);
+    }
+    if config.hull && config.corner_radius.is_some() {
+        return Err(MaskMyNameError::InvalidArgument("--hull and --rounded both shape the mask, pick one".to_string()));
+    }
+    // matched against the target names as given, so a typo would silently fall back to --mask-mode.
+    for (name, _) in &config.target_mask_modes {
+        let in_manifest = manifest.iter().flatten().any(|(_, listed)| listed.contains(name));
+        if !targets.contains(name) && !in_manifest && !config.lang_targets.iter().any(|(_, target)| target == name) {
+            return Err(MaskMyNameError::InvalidArgument(format!("--target-mask names {}, which is not a target", name)));
+        }
+    }
+    match &output {
+        None if is_stdin(Path::new(&path)) => {
+            return Err(MaskMyNameError::InvalidArgument("reading the image from stdin needs --output PATH, or --output - to write it to stdout".to_string()));
+        },
+        Some(_) if Path::new(&path).is_dir() => {
+            return Err(MaskMyNameError::InvalidArgument("--output names a single file, use --output-template for a directory".to_string()));
+        },
+        _ => {}
+    }
+    if let (Some(min), Some(max)) = (config.min_region_area, config.max_region_area) {
+        if min > max {
+            return Err(MaskMyNameError::InvalidArgument("--min-region-area is larger than --max-region-area".to_string()));
+        }
+    }
+    if let Some(tiling) = config.tiling.as_mut() {
+        if tile_overlap < 0 || tile_overlap >= tiling.size {
+            return Err(MaskMyNameError::InvalidArgument("--tile-overlap must be at least 0 and smaller than the --tiled size".to_string()));
+        }
+        tiling.overlap = tile_overlap;
+    }
+    if let Some(boxes) = boxes {
+        if detector.is_some() || config.tiling.is_some() {
+            return Err(MaskMyNameError::InvalidArgument("--boxes replaces detection and cannot be combined with --detector, --detectors, --mask-color-range or --tiled".to_string()));
+        }
+        config.detector = Detector::Boxes(boxes);
     } else {
-        80.
+        let detector = detector.unwrap_or("contour".to_string());
+        let mut names = detector.split(',').map(|name| name.trim());
+        config.detector = parse_detector(names.next().unwrap_or("contour"), east_model.as_deref(), &config)?;
+        config.extra_detectors = names.map(|name| parse_detector(name, east_model.as_deref(), &config)).collect::<Result<_, _>>()?;
+    }
+    if config.relax_attempts > 0 && !std::iter::once(&config.detector).chain(&config.extra_detectors).any(|d| *d == Detector::Contour) {
+        return Err(MaskMyNameError::InvalidArgument("--auto-relax loosens the contour detector, which is not in use".to_string()));
+    }
+    if !config.extra_detectors.is_empty() && std::iter::once(&config.detector).chain(&config.extra_detectors).any(|d| *d == Detector::ColorRange) {
+        return Err(MaskMyNameError::InvalidArgument("color-range masks without OCR and cannot be combined with other detectors".to_string()));
     }
+    // the histogram only looks at the pixels, nothing gets matched.
+    if !histogram {
+        check_targets(&targets, &config, manifest.is_some())?;
+    }
+    Ok(Cli {
+        command,
+        image_path: PathBuf::from(path),
+        targets,
+        manifest,
+        keep_going,
+        histogram,
+        output_template,
+        output,
+        no_clobber,
+        always_write,
+        quiet,
+        print_matches,
+        timings,
+        diagnose_on_failure,
+        resume,
+        csv_log,
+        db_log,
+        ocr_dump,
+        mask_layer,
+        svg_overlay,
+        segmentation,
+        segmentation_format,
+        compare,
+        diff,
+        diff_threshold,
+        annotations,
+        annotation_format,
+        srgb,
+        contact_sheet,
+        estimate,
+        deadline,
+        columns,
+        tile_size,
+        config,
+    })
 }
 
-fn mask_text(image: &Mat) -> Result<Mat, MaskMyNameError> {
-    let mut image_hsv: Mat = Default::default();
-    let mut image_mask: Mat = Default::default();
-    cvt_color(image, &mut image_hsv, COLOR_BGR2HSV, 0).expect("Convert BGR2HSV failed. check input image file.");
-    in_range(&image_hsv,
-             &Scalar::new(0., 0., 0., 0.),
-             &Scalar::new(0., 0., max_range(image.rows()), 255.),
-             &mut image_mask).expect("in_range failed. check converted Mat is in HSV Colour space.");
-    let kernel = get_structuring_element(MORPH_RECT, Size::new(5, 3), Point::new(-1, -1)).expect("failed to get_structuring_element.");
-    let mut image_dst: Mat = Default::default();
-    dilate(&image_mask, &mut image_dst, &kernel, Point::new(-1, -1), iterations(image.rows()), 0,
-           morphology_default_border_value().expect("Failed to calling morphology_default_border_value.")).expect("Failed to calling dilate.");
-    Ok(image_dst)
+/// Expands a directory into the image files it contains, sorted by name.
+/// A plain file path is returned as is.
+fn collect_images(path: &Path) -> Result<Vec<PathBuf>, MaskMyNameError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut images: Vec<PathBuf> = fs::read_dir(path).map_err(|_| MaskMyNameError::InputDirReadError())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            .unwrap_or(false))
+        .collect();
+    images.sort();
+    Ok(images)
+}
+
+const DEFAULT_OUTPUT_TEMPLATE: &str = "{stem}_masked.{ext}";
+const TEMPLATE_PLACEHOLDERS: [&str; 4] = ["stem", "ext", "index", "dir"];
+
+/// Splits a template into literal text and `{placeholder}` names, rejecting unknown or unclosed ones.
+fn parse_template(template: &str) -> Result<Vec<(bool, String)>, MaskMyNameError> {
+    let invalid = |reason: String| MaskMyNameError::InvalidArgument(format!("--output-template {}: {}", template, reason));
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Err(invalid("unmatched }".to_string()));
+        }
+        let close = rest[open..].find('}').ok_or(invalid("unclosed {".to_string()))? + open;
+        let name = &rest[open + 1..close];
+        if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+            return Err(invalid(format!("unknown placeholder {{{}}}", name)));
+        }
+        parts.push((false, rest[..open].to_string()));
+        parts.push((true, name.to_string()));
+        rest = &rest[close + 1..];
+    }
+    parts.push((false, rest.to_string()));
+    Ok(parts)
 }
 
-fn find_textarea_from_mask(image: &Mat) -> Result<Vec<Rect>, MaskMyNameError> {
-    let mut contours: VectorOfVectorOfPoint = Default::default();
-    let mut rect_result: Vec<Rect> = Default::default();
-    find_contours(image, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_NONE, Default::default()).expect("find_contours failed.");
-    for contour in contours {
-        let rect = bounding_rect(&contour).expect("");
-        if rect.height < rect.width && rect.height > (image.rows() / 72) {
-            if rect.width / rect.height < 15 && rect.width < (image.cols() / 2) {
-                rect_result.push(rect);
+/// `index` is the 1-based position of the image in the batch.
+fn output_path(template: &str, image_path: &Path, index: usize) -> Result<String, MaskMyNameError> {
+    let mut path = String::new();
+    for (placeholder, part) in parse_template(template)? {
+        if !placeholder {
+            path.push_str(&part);
+            continue;
+        }
+        match part.as_str() {
+            "stem" => { path.push_str(image_path.file_stem().unwrap_or("output".as_ref()).to_str().unwrap_or("output")); },
+            "ext" => { path.push_str(image_path.extension().unwrap_or("jpg".as_ref()).to_str().unwrap_or("jpg")); },
+            "index" => { path.push_str(&index.to_string()); },
+            _ => {
+                let dir = image_path.parent().and_then(|p| p.to_str()).unwrap_or("");
+                path.push_str(if dir.is_empty() { "." } else { dir });
             }
         }
     }
-    Ok(rect_result)
+    Ok(path)
 }
 
-fn scan_image(tess: &mut TessBaseApi, image: &Mat) -> Result<Text, MaskMyNameError> {
-    tess.set_image(image.data_bytes().expect("Failed to get data_bytes from image Mat."),
-                   image.cols() as c_int,
-                   image.rows() as c_int,
-                   image.channels(), (image.cols() * image.channels()) as c_int).expect("Set image to Tesseract failed.");
-    match tess.get_utf8_text() {
-        Ok(text) => { Ok(text) },
-        Err(_) => { Err(MaskMyNameError::TessGetTextError()) }
+/// Checked before an image is processed, so a refused output costs no OCR.
+fn check_clobber(path: &str, no_clobber: bool) -> Result<(), MaskMyNameError> {
+    match no_clobber && path != STDOUT_PATH && Path::new(path).exists() {
+        true => { Err(MaskMyNameError::OutputExists(path.to_string())) },
+        false => { Ok(()) }
     }
 }
 
-fn masking_bar(roi: &Mat) -> Result<Mat, MaskMyNameError> {
-    match Mat::new_rows_cols_with_default(roi.rows(), roi.cols(), roi.typ(), Scalar::new(255., 255., 255., 255.)) {
-        Ok(mat) => { Ok(mat) }
-        Err(_) => { Err(MaskMyNameError::MaskingBarCreationError()) }
+/// Encoded by the extension of `path`, as PNG for `STDOUT_PATH`.
+fn encode(path: &str, image: &Mat) -> Result<Vec<u8>, MaskMyNameError> {
+    let extension = match path {
+        STDOUT_PATH => { "png" },
+        _ => { Path::new(path).extension().and_then(|e| e.to_str()).ok_or(MaskMyNameError::ImageWriteError())? }
+    };
+    let mut encoded = VectorOfu8::new();
+    match imencode(&format!(".{}", extension), image, &mut encoded, &Default::default()) {
+        Ok(true) => { Ok(encoded.to_vec()) },
+        _ => { Err(MaskMyNameError::ImageWriteError()) }
     }
 }
 
-fn init_tess(lang: &CStr) -> Result<TessBaseApi, MaskMyNameError> {
-    let mut ocr = TessBaseApi::create();
-    match ocr.init_2(None, Some(lang)) {
-        Ok(_) => { Ok(ocr) },
-        Err(_) => { Err(MaskMyNameError::TessInitError()) }
+fn write_bytes(path: &str, bytes: &[u8]) -> Result<(), MaskMyNameError> {
+    match path {
+        STDOUT_PATH => { io::stdout().lock().write_all(bytes).map_err(|_| MaskMyNameError::ImageWriteError()) },
+        _ => { fs::write(path, bytes).map_err(|_| MaskMyNameError::ImageWriteError()) }
     }
 }
 
-fn supplement_target_string(target: &String) -> Vec<String> {
-    let mut strings = Vec::new();
-    match target.contains("_") {
-        true => {
-            strings.push(target.to_lowercase());
-            strings.push(target.replace("_", " ").to_lowercase());
-        }
-        false => {
-            strings.push(target.to_lowercase());
-        }
-    }
-    strings
-}
-
-fn mask_my_name(lang: CString, image_path: &PathBuf, target_string: &String) -> Result<Mat, MaskMyNameError> {
-    let mut success = false;
-    let image = load_image(image_path).expect("Can't load image file from path.");
-    let mut target_image: Mat = Default::default();
-    let strings = supplement_target_string(target_string);
-    match init_tess(lang.as_c_str()) {
-        Ok(mut tess) => {
-            for area in find_textarea_from_mask(&mask_text(&image)?)? {
-                let mut roi = Mat::roi(&image, area).expect("Failed to create ROI.");
-                roi.copy_to(&mut target_image).expect("Failed to copy roi data.");
-                match scan_image(&mut tess, &target_image) {
-                    Ok(text) => {
-                        let picked = text.as_ref().to_str().unwrap_or("").to_lowercase().replace(".", "").replace(",", "");
-                        if strings.iter().any(|s| picked.contains(s)) {
-                            success = true;
-                            masking_bar(&roi)?.copy_to(&mut roi).expect("Failed to copy black bar data.");
-                        }
-                    },
-                    Err(e) => { return Err(e); }
-                }
+fn write_image(path: &str, image: &Mat) -> Result<(), MaskMyNameError> {
+    if path == STDOUT_PATH {
+        return write_bytes(path, &encode(path, image)?);
+    }
+    match imwrite(path, image, &Default::default()) {
+        Ok(true) => { Ok(()) },
+        _ => { Err(MaskMyNameError::ImageWriteError()) }
+    }
+}
+
+/// `write_image` with the encoded bytes tagged by `color_profile::tag`, taking the profile from `source` when it has one.
+fn write_tagged(path: &str, image: &Mat, source: Option<&[u8]>) -> Result<(), MaskMyNameError> {
+    write_bytes(path, &color_profile::tag(encode(path, image)?, source))
+}
+
+/// Inputs listed in the resume state at `state_path`, one per line. A missing file is a fresh start.
+fn load_resume_state(state_path: &Path) -> Result<Vec<String>, MaskMyNameError> {
+    match fs::read_to_string(state_path) {
+        Ok(contents) => { Ok(contents.lines().filter(|line| !line.is_empty()).map(|line| line.to_string()).collect()) },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => { Ok(Vec::new()) },
+        Err(_) => { Err(MaskMyNameError::ResumeStateError(state_path.display().to_string())) }
+    }
+}
+
+/// Appends `image_path` to the resume state right away, so a crash loses at most the image being worked on.
+fn record_done(state_path: &Path, image_path: &Path) -> Result<(), MaskMyNameError> {
+    let state_error = |_| MaskMyNameError::ResumeStateError(state_path.display().to_string());
+    let mut state = fs::OpenOptions::new().create(true).append(true).open(state_path).map_err(state_error)?;
+    writeln!(state, "{}", image_path.display()).and_then(|_| state.sync_data()).map_err(state_error)
+}
+
+fn print_timings(args: &Cli, label: &str, timings: &Timings) {
+    let ms = |d: Duration| d.as_secs_f64() * 1000.;
+    report!(args, "{}: load {:.1} ms, mask_text {:.1} ms, find regions {:.1} ms, ocr {:.1} ms, write {:.1} ms", label,
+            ms(timings.load), ms(timings.mask_text), ms(timings.find_regions), ms(timings.ocr), ms(timings.write));
+}
+
+/// What was detected and read in an image without a match, so the user can see why nothing matched.
+fn print_diagnosis(image_path: &Path, masked: &MaskedImage) {
+    if masked.regions.is_empty() {
+        eprintln!("{}: no text regions were detected.", image_path.display());
+        return;
+    }
+    eprintln!("{}: {} region(s) detected, none matched:", image_path.display(), masked.regions.len());
+    for (area, text) in &masked.regions {
+        eprintln!("  {} \"{}\"", format_rect(area), text.replace('\n', " "));
+    }
+}
+
+/// Paths of the files written next to the masked image, from their templates.
+struct ExtraOutputs {
+    layer: Option<String>,
+    svg: Option<String>,
+    segmentation: Option<String>,
+    compare: Option<String>,
+    diff: Option<String>,
+    annotations: Option<String>,
+}
+
+impl ExtraOutputs {
+    fn new(args: &Cli, image_path: &Path, index: usize) -> Result<ExtraOutputs, MaskMyNameError> {
+        let path = |template: &Option<String>| template.as_deref().map(|t| output_path(t, image_path, index)).transpose();
+        Ok(ExtraOutputs {
+            layer: path(&args.mask_layer)?,
+            svg: path(&args.svg_overlay)?,
+            segmentation: path(&args.segmentation)?,
+            compare: path(&args.compare)?,
+            diff: path(&args.diff)?,
+            annotations: path(&args.annotations)?,
+        })
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &String> {
+        self.layer.iter().chain(&self.svg).chain(&self.segmentation).chain(&self.compare).chain(&self.diff).chain(&self.annotations)
+    }
+}
+
+fn warn_suspicions(image_path: &Path, masked: &MaskedImage, config: &MaskConfig) -> Result<(), MaskMyNameError> {
+    for suspicion in suspicions(masked, config)? {
+        match suspicion {
+            Suspicion::NoRegions(density) => {
+                eprintln!("{}: warning: no text regions detected, but {:.0}% of the image are edges as in text-heavy images. Detection may be too strict, try --hsv-upper, --min-height or another --detector.",
+                          image_path.display(), density * 100.);
+            },
+            Suspicion::NearMisses(target, areas) => {
+                let areas: Vec<String> = areas.iter().map(format_rect).collect();
+                eprintln!("{}: warning: \"{}\" matched once, but {} other region(s) read almost like it and may be missed occurrences: {}. Check them or try --fuzzy.",
+                          image_path.display(), target, areas.len(), areas.join(" "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Exit code of a run stopped by `--deadline`, as `timeout(1)` uses.
+const DEADLINE_EXIT_CODE: u8 = 124;
+
+fn past(deadline: Option<Instant>) -> bool {
+    deadline.map(|deadline| Instant::now() >= deadline).unwrap_or(false)
+}
+
+/// Progress that stops the image being masked once `deadline` has passed.
+fn deadline_progress(deadline: Option<Instant>) -> impl FnMut(usize, usize) -> ControlFlow<()> {
+    move |_, _| match past(deadline) {
+        true => { ControlFlow::Break(()) },
+        false => { ControlFlow::Continue(()) }
+    }
+}
+
+fn process_image(image_path: &Path, targets: &[String], output_path: &str, extras: &ExtraOutputs, args: &Cli) -> Result<Timings, MaskMyNameError> {
+    let masked = mask_my_name_with_coverage(image_path, targets, &args.config, &mut deadline_progress(args.deadline))?;
+    if args.diagnose_on_failure && masked.coverage() == Coverage::NoMatch {
+        print_diagnosis(image_path, &masked);
+    }
+    if let Some(dump_path) = &args.ocr_dump {
+        redaction_log::append_ocr_dump(dump_path, image_path, &masked)?;
+    }
+    // warned about before the coverage check, an image without any region never gets past it.
+    if !args.quiet {
+        warn_suspicions(image_path, &masked, &args.config)?;
+    }
+    // every image is annotated, with a match or not.
+    if let Some(annotation_path) = &extras.annotations {
+        annotations::write_annotations(annotation_path, image_path, &masked, args.annotation_format)?;
+    }
+    // a partial match with --require-all still fails, the targets were there but not all masked.
+    let unmatched = args.always_write && masked.coverage() == Coverage::NoMatch;
+    let mut masked = match unmatched {
+        true => { masked },
+        false => { check_coverage(masked, &args.config)? }
+    };
+    if unmatched {
+        match masked.masked.is_empty() && args.config.blur_background.is_none() {
+            true => { report!(args, "No match, writing the image unmodified."); },
+            false => { report!(args, "No match, writing the image modified anyway, {} region(s) masked.", masked.masked.len()); }
+        }
+    } else if args.print_matches {
+        let mut printed: Vec<&str> = Vec::new();
+        for m in &masked.matches {
+            if !printed.contains(&m.text.as_str()) {
+                report!(args, "{}", m.text);
+                printed.push(&m.text);
             }
-            match success {
-                true => { Ok(image) },
-                false => { Err(MaskMyNameError::NoMatchingString()) }
+        }
+    } else if !args.diagnose_on_failure {
+        report!(args, "Matching found. write masked image to disk.");
+        for m in &masked.matches {
+            match args.config.match_mode {
+                MatchMode::Fuzzy(_) => { report!(args, "  {} \"{}\" -> \"{}\" ({:.2})", format_rect(&m.area), m.text, m.target, m.score); },
+                _ => { report!(args, "  {} \"{}\" -> \"{}\"", format_rect(&m.area), m.text, m.target); }
             }
-        },
-        Err(e) => { Err(e) }
+        }
+        for code in &masked.codes {
+            report!(args, "  {} QR code \"{}\"", format_rect(&code.area), code.payload);
+        }
+        report!(args, "{} region(s) masked.", masked.masked.len());
+        if !masked.detector_counts.is_empty() {
+            let counts: Vec<String> = masked.detector_counts.iter().map(|(name, count)| format!("{} {}", name, count)).collect();
+            report!(args, "Regions per detector: {}", counts.join(", "));
+        }
+        if masked.flat_regions > 0 {
+            report!(args, "{} region(s) below contrast {} skipped without OCR.", masked.flat_regions, args.config.min_region_contrast.unwrap_or(0.));
+        }
+        if masked.rescanned > 0 {
+            report!(args, "{} region scan(s) below confidence {} repeated upscaled.", masked.rescanned, args.config.rescan_below.unwrap_or(0));
+        }
+        if !masked.low_contrast.is_empty() && args.config.contrast_check == Some(ContrastCheck::Switch) {
+            let areas: Vec<String> = masked.low_contrast.iter().map(format_rect).collect();
+            report!(args, "{} mask(s) switched to black or white for contrast with their background: {}", areas.len(), areas.join(" "));
+        }
+        if let Some(relaxation) = masked.relaxed {
+            report!(args, "Matched after relaxing detection {} time(s): HSV upper bounds raised by {}, minimum region height {} px.",
+                    relaxation.attempt, relaxation.hsv_widened_by, relaxation.min_height);
+        }
+        if masked.capped {
+            report!(args, "Stopped at {} matches, the remaining regions were not checked.", masked.matches.len());
+        }
+        if masked.coverage() == Coverage::Partial {
+            report!(args, "Partial match, not found: {}", masked.missing_targets().join(", "));
+        }
+    }
+    if masked.ocr_retried > 0 {
+        println!("{} OCR scan(s) retried after a failure.", masked.ocr_retried);
+    }
+    if masked.ocr_retried > 0 {
+        println!("{} OCR scan(s) retried after a failure.", masked.ocr_retried);
+    }
+    // a warning, so shown whatever the summary settings.
+    if !masked.low_contrast.is_empty() && args.config.contrast_check == Some(ContrastCheck::Warn) {
+        let areas: Vec<String> = masked.low_contrast.iter().map(format_rect).collect();
+        eprintln!("{}: {} mask(s) barely stand out from their background, use --auto-contrast or another --mask-mode: {}",
+                  image_path.display(), areas.len(), areas.join(" "));
+    }
+    if let Some(layer_path) = &extras.layer {
+        write_image(layer_path, &mask_layer(&masked)?)?;
+    }
+    if let Some(svg_path) = &extras.svg {
+        svg::write_overlay(svg_path, &masked)?;
+    }
+    if let Some(segmentation_path) = &extras.segmentation {
+        mask_export::write_segmentation(segmentation_path, &masked, args.segmentation_format)?;
+    }
+    if let Some(compare_path) = &extras.compare {
+        write_image(compare_path, &side_by_side(&masked)?)?;
     }
+    if let Some(diff_path) = &extras.diff {
+        write_image(diff_path, &difference(&masked, args.diff_threshold)?)?;
+    }
+    // freed before the output is encoded, the unmasked copy is only needed for the above.
+    masked.original = None;
+    let started = Instant::now();
+    match args.srgb {
+        // a URL is not read again just for its profile.
+        true => { write_tagged(output_path, &masked.image, fs::read(image_path).ok().as_deref())? },
+        false => { write_image(output_path, &masked.image)? }
+    }
+    masked.timings.write = started.elapsed();
+    if args.diagnose_on_failure {
+        report!(args, "{}", output_path);
+    }
+    if args.timings {
+        print_timings(args, "timings", &masked.timings);
+    }
+    // logged only once written, so the log never lists a redaction that did not happen.
+    if let Some(csv_path) = &args.csv_log {
+        redaction_log::append_csv(csv_path, image_path, &masked)?;
+    }
+    if let Some(db_path) = &args.db_log {
+        append_db(db_path, &[(image_path.display().to_string(), &masked.matches, &masked.codes)])?;
+    }
+    Ok(masked.timings)
 }
 
-struct Cli {
-    image_path: PathBuf,
-    target_string: String
+fn print_histogram(image_path: &Path, config: &MaskConfig) -> Result<(), MaskMyNameError> {
+    const BUCKET: usize = 8;
+    const BAR_WIDTH: f32 = 50.;
+    let (bins, threshold) = value_histogram(image_path, config)?;
+    let buckets: Vec<f32> = bins.chunks(BUCKET).map(|chunk| chunk.iter().sum()).collect();
+    let peak = buckets.iter().cloned().fold(1., f32::max);
+    println!("{}: HSV value histogram (text threshold is V <= {})", image_path.display(), threshold);
+    for (i, count) in buckets.iter().enumerate() {
+        println!("  {:>3}-{:<3} | {:<50} {}", i * BUCKET, (i + 1) * BUCKET - 1,
+                 "#".repeat((count / peak * BAR_WIDTH).round() as usize), *count as u64);
+    }
+    Ok(())
 }
 
-fn main() {
-    let path = env::args().nth(1).expect("no path given");
-    let target_string = env::args().nth(2).unwrap_or("".to_string());
-    let args = Cli {
-        image_path: PathBuf::from(path),
-        target_string,
+#[cfg(feature = "sqlite")]
+fn append_db(db_path: &Path, records: &[(String, &[RegionMatch], &[CodeMatch])]) -> Result<(), MaskMyNameError> {
+    redaction_log::append_db_rows(db_path, records)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn append_db(db_path: &Path, _records: &[(String, &[RegionMatch], &[CodeMatch])]) -> Result<(), MaskMyNameError> {
+    Err(MaskMyNameError::DbWriteError(format!("{}: this build has no SQLite support, rebuild with --features sqlite", db_path.display())))
+}
+
+/// Size buckets of `run_estimate`, one image timed per bucket.
+const ESTIMATE_BUCKETS: usize = 5;
+
+/// Splits the still images, sorted by file size, into `ESTIMATE_BUCKETS` buckets, masks the median image of each
+/// without writing it, and prints the total time that extrapolates to, each bucket weighed by its image count.
+fn run_estimate(args: &Cli, images: &[PathBuf]) -> Result<(), MaskMyNameError> {
+    let file_size = |path: &PathBuf| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let (animations, mut stills): (Vec<&PathBuf>, Vec<&PathBuf>) = images.iter().partition(|path| animation::is_animation(path));
+    stills.sort_by_key(|path| file_size(path));
+    let mut config = args.config.clone();
+    config.crops_dir = None;
+    config.interactive = false;
+    let (mut sampled, mut timed_images, mut total) = (0, 0, Duration::ZERO);
+    println!("Timing up to {} of {} still image(s), nothing is written:", ESTIMATE_BUCKETS.min(stills.len()), stills.len());
+    let mut average = Timings::default();
+    for bucket in stills.chunks(stills.len().div_ceil(ESTIMATE_BUCKETS).max(1)) {
+        let sample = bucket[bucket.len() / 2];
+        let started = Instant::now();
+        match mask_my_name_with_coverage(sample, &args.targets, &config, &mut |_, _| ControlFlow::Continue(())) {
+            Ok(masked) => {
+                let elapsed = started.elapsed();
+                println!("  {} ({} KiB): {:.1} s, standing for {} image(s)", sample.display(), file_size(sample) / 1024,
+                         elapsed.as_secs_f64(), bucket.len());
+                total += elapsed * bucket.len() as u32;
+                average.add(&masked.timings);
+                sampled += 1;
+                timed_images += bucket.len();
+            },
+            Err(e) => { eprintln!("  {}: {}, bucket left out of the estimate", sample.display(), e); }
+        }
+    }
+    if timed_images == 0 {
+        return Err(MaskMyNameError::InvalidArgument("no still image could be timed for --estimate".to_string()));
+    }
+    // buckets whose sample failed are assumed to take as long as the others on average.
+    let total = total.mul_f64(stills.len() as f64 / timed_images as f64);
+    println!("Estimated {:.0} s for {} image(s), {:.1} s per image, from {} sample(s).", total.as_secs_f64(), stills.len(),
+             total.as_secs_f64() / stills.len() as f64, sampled);
+    if args.timings {
+        let per_sample = |d: Duration| d / sampled as u32;
+        print_timings(args, "average per sample", &Timings {
+            load: per_sample(average.load),
+            mask_text: per_sample(average.mask_text),
+            find_regions: per_sample(average.find_regions),
+            ocr: per_sample(average.ocr),
+            write: Duration::ZERO,
+        });
+    }
+    if !animations.is_empty() {
+        println!("{} animation(s) not estimated, they take about a still image's time per frame.", animations.len());
+    }
+    Ok(())
+}
+
+fn run_tune(args: &Cli, labels_path: &Path, sweep: &[f64]) -> Result<(), MaskMyNameError> {
+    let labels = tune::load_labels(labels_path)?;
+    let results = tune::tune(&args.image_path, &labels, &args.targets, &args.config, sweep)?;
+    println!("{} labelled image(s).", labels.len());
+    println!("threshold  precision  recall  f1     tp  fp  fn  tn");
+    for (threshold, scores) in &results {
+        let threshold = threshold.map(|t| format!("{:.3}", t)).unwrap_or("current".to_string());
+        println!("{:<9}  {:<9.3}  {:<6.3}  {:<5.3}  {:<2}  {:<2}  {:<2}  {}", threshold, scores.precision(), scores.recall(), scores.f1(),
+                 scores.true_positives, scores.false_positives, scores.false_negatives, scores.true_negatives);
+    }
+    // earlier, i.e. lower, thresholds win ties.
+    let best = results.iter().filter_map(|(t, scores)| t.map(|t| (t, scores.f1())))
+        .fold(None, |best: Option<(f64, f64)>, (t, f1)| match best {
+            Some((_, best_f1)) if best_f1 >= f1 => { best },
+            _ => { Some((t, f1)) }
+        });
+    if let Some((threshold, f1)) = best {
+        println!("Best threshold {:.3} (f1 {:.3}), use --fuzzy {:.3}.", threshold, f1, threshold);
+    }
+    Ok(())
+}
+
+fn report_animation(image_path: &Path, summary: &animation::AnimationSummary) {
+    println!("{}: {} frames, {} with matches, {} regions matched.",
+             image_path.display(), summary.frames, summary.matched_frames, summary.regions);
+    if summary.held_frames > 0 {
+        println!("Masks held over from earlier frames in {} frame(s).", summary.held_frames);
+    }
+    println!("Matching found. write masked image to disk.");
+}
+
+/// Masks every image inside the ZIP input, returning how many entries failed.
+/// Fails with `Cancelled` once `--deadline` has passed, after writing the entries masked so far.
+#[cfg(feature = "zip")]
+fn run_archive(args: &Cli) -> Result<usize, MaskMyNameError> {
+    use mask_my_name::archive::{mask_archive, EntryResult};
+    let output = match &args.output {
+        Some(output) if output == STDOUT_PATH => { return Err(MaskMyNameError::InvalidArgument("archives cannot be written to stdout".to_string())); },
+        Some(output) => { output.clone() },
+        None => { output_path("{dir}/{stem}_masked.zip", &args.image_path, 1)? }
     };
-    if !&args.image_path.is_file() {
-        panic!("Image file not exist! check your input filename or path.")
-    }
-    match mask_my_name(CString::new("eng").expect("Convert str to CString failed."), &args.image_path, &args.target_string) {
-        Ok(image) => {
-            println!("Matching found. write masked image to disk.");
-            imwrite(format!("{}_masked.{}",
-                            &args.image_path.file_stem().unwrap_or("output".as_ref()).to_str().unwrap_or("output"),
-                            &args.image_path.extension().unwrap_or("jpg".as_ref()).to_str().unwrap_or("jpg")).as_str(),
-                    &image, &Default::default()).expect("Failed to write image data.");
-        },
+    check_clobber(&output, args.no_clobber)?;
+    let mut failed = 0;
+    let mut stopped = false;
+    mask_archive(&args.image_path, Path::new(&output), &args.targets, &args.config, &mut deadline_progress(args.deadline), &mut |name, result| {
+        match result {
+            // interrupted between regions, the entry was not written.
+            EntryResult::Failed(MaskMyNameError::Cancelled()) if past(args.deadline) => {
+                stopped = true;
+                return ControlFlow::Break(());
+            },
+            EntryResult::Masked(masked) => {
+                println!("{}: {} region(s) masked.", name, masked.masked.len());
+                let entry_path = args.image_path.join(name);
+                let logged = match &args.csv_log {
+                    Some(csv_path) => { redaction_log::append_csv(csv_path, &entry_path, masked) },
+                    None => { Ok(()) }
+                }.and_then(|_| match &args.db_log {
+                    Some(db_path) => { append_db(db_path, &[(entry_path.display().to_string(), &masked.matches, &masked.codes)]) },
+                    None => { Ok(()) }
+                });
+                if let Err(e) = logged {
+                    eprintln!("{}: {}", name, e);
+                    failed += 1;
+                    if !args.keep_going {
+                        return ControlFlow::Break(());
+                    }
+                }
+            },
+            EntryResult::Failed(MaskMyNameError::NoMatchingString()) => { println!("{}: {}", name, MaskMyNameError::NoMatchingString()); },
+            EntryResult::Failed(e) => {
+                eprintln!("{}: {}", name, e);
+                failed += 1;
+                if !args.keep_going {
+                    return ControlFlow::Break(());
+                }
+            },
+            EntryResult::Skipped(reason) => { println!("{}: skipped, {}.", name, reason); }
+        }
+        stopped = past(args.deadline);
+        match stopped {
+            true => { ControlFlow::Break(()) },
+            false => { ControlFlow::Continue(()) }
+        }
+    })?;
+    println!("{}", output);
+    match stopped {
+        true => { Err(MaskMyNameError::Cancelled()) },
+        false => { Ok(failed) }
+    }
+}
+
+#[cfg(not(feature = "zip"))]
+fn run_archive(args: &Cli) -> Result<usize, MaskMyNameError> {
+    Err(MaskMyNameError::InvalidArgument(format!("{}: reading ZIP archives needs a build with --features zip", args.image_path.display())))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => { args },
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    if let Command::Help = args.command {
+        print!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if let Command::ListLangs = args.command {
+        return match available_languages(args.config.tessdata_dir.as_deref()) {
+            Ok(languages) => {
+                for language in languages {
+                    println!("{}", language);
+                }
+                ExitCode::SUCCESS
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if let Command::Tune { labels, sweep } = &args.command {
+        return match run_tune(&args, labels, sweep) {
+            Ok(_) => { ExitCode::SUCCESS },
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if !is_url(&args.image_path) && !is_stdin(&args.image_path) && !args.image_path.is_file() && !args.image_path.is_dir() {
+        eprintln!("Image file not exist! check your input filename or path.");
+        return ExitCode::from(2);
+    }
+    if is_archive(&args.image_path) && args.image_path.is_file() {
+        return match run_archive(&args) {
+            Ok(0) => { ExitCode::SUCCESS },
+            Ok(failed) => {
+                eprintln!("{} archive entries failed.", failed);
+                ExitCode::FAILURE
+            },
+            Err(MaskMyNameError::Cancelled()) if past(args.deadline) => {
+                eprintln!("Deadline passed, the remaining archive entries were not processed.");
+                ExitCode::from(DEADLINE_EXIT_CODE)
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let images = match collect_images(&args.image_path) {
+        Ok(images) => { images },
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.estimate {
+        return match run_estimate(&args, &images) {
+            Ok(_) => { ExitCode::SUCCESS },
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if let Some(sheet_path) = &args.contact_sheet {
+        let written = check_clobber(sheet_path, args.no_clobber).and_then(|_| contact_sheet::contact_sheet(&images, &args.targets, &args.config, args.columns, args.tile_size))
+            .and_then(|(sheet, tile_matches)| {
+                for (index, (image_path, matches)) in images.iter().zip(tile_matches).enumerate() {
+                    println!("tile {}: {}: {} regions matched.", index + 1, image_path.display(), matches);
+                }
+                write_image(sheet_path, &sheet)
+            });
+        return match written {
+            Ok(_) => { ExitCode::SUCCESS },
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut failures: Vec<(PathBuf, MaskMyNameError)> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+    let mut total_timings = Timings::default();
+    let mut timed = 0;
+    let done = match args.resume.as_deref().map(load_resume_state).transpose() {
+        Ok(done) => { done.unwrap_or_default() },
         Err(e) => {
-            match e {
-                MaskMyNameError::NoMatchingString() => {
-                    // TODO: switch to japanese string
-                    println!("{}", e);
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut unfinished = 0;
+    let mut unlisted: Vec<String> = Vec::new();
+    let total = images.len();
+    for (index, image_path) in images.into_iter().enumerate() {
+        if done.contains(&image_path.display().to_string()) {
+            report!(args, "{}: done in an earlier run, skipped.", image_path.display());
+            continue;
+        }
+        if past(args.deadline) {
+            unfinished = total - index;
+            break;
+        }
+        let result = if args.histogram {
+            print_histogram(&image_path, &args.config)
+        } else {
+            let output = match &args.output {
+                Some(output) => { Ok(output.clone()) },
+                None => { output_path(&args.output_template, &image_path, index + 1) }
+            };
+            output.and_then(|output| {
+                let extras = ExtraOutputs::new(&args, &image_path, index + 1)?;
+                check_clobber(&output, args.no_clobber)?;
+                for extra in extras.paths() {
+                    check_clobber(extra, args.no_clobber)?;
+                }
+                let targets = match args.manifest.as_ref().map(|manifest| manifest.iter().find(|(path, _)| *path == image_path)) {
+                    Some(Some((_, targets))) => { targets },
+                    Some(None) => {
+                        unlisted.push(image_path.display().to_string());
+                        // with no target of its own, nothing but --redact-all and the like could match.
+                        check_targets(&args.targets, &args.config, false)?;
+                        &args.targets
+                    },
+                    None => { &args.targets }
+                };
+                match animation::is_animation(&image_path) {
+                    true if output == STDOUT_PATH => { Err(MaskMyNameError::InvalidArgument("animations cannot be written to stdout".to_string())) },
+                    true => {
+                        let summary = animation::process_animation(&image_path, &output, targets, &args.config, &mut deadline_progress(args.deadline))?;
+                        report_animation(&image_path, &summary);
+                        // logged only once written, like a still image.
+                        let records: Vec<(String, &[RegionMatch], &[CodeMatch])> = summary.frame_matches.iter()
+                            .map(|frame| (format!("{}#frame{}", image_path.display(), frame.frame), frame.matches.as_slice(), frame.codes.as_slice()))
+                            .collect();
+                        if let Some(csv_path) = &args.csv_log {
+                            for (file_name, matches, codes) in &records {
+                                redaction_log::append_csv_rows(csv_path, file_name, matches, codes)?;
+                            }
+                        }
+                        if let Some(db_path) = &args.db_log {
+                            append_db(db_path, &records)?;
+                        }
+                        Ok(())
+                    },
+                    false => {
+                        total_timings.add(&process_image(&image_path, targets, &output, &extras, &args)?);
+                        timed += 1;
+                        Ok(())
+                    }
+                }
+            })
+        };
+        match result {
+            Ok(_) => {},
+            Err(MaskMyNameError::NoMatchingString()) => {
+                // TODO: switch to japanese string
+                report!(args, "{}: {}", image_path.display(), MaskMyNameError::NoMatchingString());
+                // nothing was written, which a pipe reading stdout must not take for success.
+                if args.output.as_deref() == Some(STDOUT_PATH) {
+                    return ExitCode::FAILURE;
                 }
-                _ => {
-                    println!("{}", e);
+            },
+            Err(MaskMyNameError::OutputExists(output)) => {
+                report!(args, "{}: skipped, {} already exists.", image_path.display(), output);
+                skipped.push(output);
+            },
+            // interrupted between regions, nothing of it was written.
+            Err(MaskMyNameError::Cancelled()) if past(args.deadline) => {
+                unfinished = total - index;
+                break;
+            },
+            Err(e) => {
+                eprintln!("{}: {}", image_path.display(), e);
+                failures.push((image_path, e));
+                if !args.keep_going {
+                    break;
                 }
+                continue;
             }
         }
+        // failed inputs are left out so the next run tries them again.
+        if let Some(state_path) = &args.resume {
+            if let Err(e) = record_done(state_path, &image_path) {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if args.timings && timed > 1 {
+        let average = |d: Duration| d / timed;
+        print_timings(&args, &format!("average over {} images", timed), &Timings {
+            load: average(total_timings.load),
+            mask_text: average(total_timings.mask_text),
+            find_regions: average(total_timings.find_regions),
+            ocr: average(total_timings.ocr),
+            write: average(total_timings.write),
+        });
+    }
+    if !unlisted.is_empty() {
+        report!(args, "{} image(s) not in the manifest, masked with the global targets:", unlisted.len());
+        for image_path in &unlisted {
+            report!(args, "  {}", image_path);
+        }
+    }
+    if !skipped.is_empty() {
+        report!(args, "{} output(s) already existed and were not overwritten:", skipped.len());
+        for output in &skipped {
+            report!(args, "  {}", output);
+        }
+    }
+    if !failures.is_empty() {
+        eprintln!("{} image(s) failed:", failures.len());
+        for (image_path, e) in &failures {
+            eprintln!("  {}: {}", image_path.display(), e);
+        }
+    }
+    if unfinished > 0 {
+        eprintln!("Deadline passed, {} of {} image(s) not processed.", unfinished, total);
+        return ExitCode::from(DEADLINE_EXIT_CODE);
+    }
+    match failures.is_empty() {
+        true => { ExitCode::SUCCESS },
+        false => { ExitCode::FAILURE }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_template_fills_every_placeholder() {
+        let image_path = Path::new("scans/page.png");
+        assert_eq!(output_path(DEFAULT_OUTPUT_TEMPLATE, image_path, 1).unwrap(), "page_masked.png");
+        assert_eq!(output_path("{dir}/out/{index}_{stem}.{ext}", image_path, 7).unwrap(), "scans/out/7_page.png");
+        // a bare file name lives in the current directory.
+        assert_eq!(output_path("{dir}/{stem}.jpg", Path::new("page.png"), 1).unwrap(), "./page.jpg");
+    }
+
+    #[test]
+    fn output_template_rejects_unknown_and_unbalanced_placeholders() {
+        for template in ["{name}.png", "{stem.png", "stem}.png"] {
+            assert!(matches!(parse_template(template), Err(MaskMyNameError::InvalidArgument(_))), "{}", template);
+        }
+        assert_eq!(parse_template("{stem}_x").unwrap(),
+                   vec![(false, "".to_string()), (true, "stem".to_string()), (false, "_x".to_string())]);
+    }
+
+    #[test]
+    fn max_aspect_ratio_must_be_a_positive_number() {
+        assert_eq!(parse_positive("--max-aspect-ratio", "7.5"), Ok(7.5));
+        for value in ["0", "-3", "nan", "NaN", "inf", "ten"] {
+            assert!(parse_positive("--max-aspect-ratio", value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn bar_scale_must_be_a_positive_number() {
+        assert_eq!(parse_positive("--bar-scale", "1.5"), Ok(1.5));
+        for value in ["0", "-1", "nan", "inf"] {
+            assert!(parse_positive("--bar-scale", value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn deadlines_must_be_positive_and_representable() {
+        assert!(parse_deadline("--deadline", "1.5").unwrap() > Instant::now());
+        for value in ["0", "-1", "nan", "inf", "1e300", "soon"] {
+            assert!(matches!(parse_deadline("--deadline", value), Err(MaskMyNameError::InvalidArgument(_))), "{}", value);
+        }
+    }
+
+    #[test]
+    fn stamp_scale_must_be_a_positive_number() {
+        assert_eq!(parse_positive("--stamp-scale", "0.5"), Ok(0.5));
+        for value in ["0", "-0.5", "nan", "inf"] {
+            assert!(parse_positive("--stamp-scale", value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn empty_targets_are_refused_unless_everything_is_masked() {
+        let config = MaskConfig::default();
+        assert!(matches!(check_targets(&["".to_string()], &config, false), Err(MaskMyNameError::EmptyTarget())));
+        assert!(matches!(check_targets(&["  ".to_string()], &config, false), Err(MaskMyNameError::EmptyTarget())));
+        assert!(matches!(check_targets(&[], &config, false), Err(MaskMyNameError::InvalidArgument(_))));
+        assert!(check_targets(&[], &config, true).is_ok());
+        assert!(check_targets(&["Jane Doe".to_string()], &config, false).is_ok());
+        let redact_all = MaskConfig { redact_all: true, ..Default::default() };
+        assert!(check_targets(&[], &redact_all, false).is_ok());
+        assert!(check_targets(&["".to_string()], &redact_all, false).is_ok());
+        let inverted = MaskConfig { redact_all: true, invert_mask: true, ..Default::default() };
+        assert!(matches!(check_targets(&[], &inverted, false), Err(MaskMyNameError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn resume_state_lists_every_recorded_input() {
+        let state_path = env::temp_dir().join(format!("mask-my-name-resume-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&state_path);
+        assert_eq!(load_resume_state(&state_path), Ok(Vec::new()));
+        record_done(&state_path, Path::new("scans/a.png")).unwrap();
+        record_done(&state_path, Path::new("scans/b.png")).unwrap();
+        assert_eq!(load_resume_state(&state_path), Ok(vec!["scans/a.png".to_string(), "scans/b.png".to_string()]));
+        fs::remove_file(&state_path).unwrap();
+        // a directory is neither a missing file nor readable state.
+        assert!(matches!(load_resume_state(&env::temp_dir()), Err(MaskMyNameError::ResumeStateError(_))));
+    }
+
+    #[test]
+    fn kernel_shapes_map_to_their_structuring_elements() {
+        assert_eq!(parse_kernel_shape("--kernel-shape", "rect").unwrap(), MORPH_RECT);
+        assert_eq!(parse_kernel_shape("--kernel-shape", "ellipse").unwrap(), MORPH_ELLIPSE);
+        assert_eq!(parse_kernel_shape("--kernel-shape", "cross").unwrap(), MORPH_CROSS);
+        assert!(matches!(parse_kernel_shape("--kernel-shape", "Rect"), Err(MaskMyNameError::InvalidArgument(_))));
+        assert_eq!(MaskConfig::default().kernel_shape, MORPH_RECT);
+    }
+
+    #[test]
+    fn contour_approximations_parse_with_simple_as_the_default() {
+        assert_eq!(parse_contour_approx("--contour-approx", "simple").unwrap(), CHAIN_APPROX_SIMPLE);
+        assert_eq!(parse_contour_approx("--contour-approx", "none").unwrap(), CHAIN_APPROX_NONE);
+        assert!(matches!(parse_contour_approx("--contour-approx", "tc89"), Err(MaskMyNameError::InvalidArgument(_))));
+        assert_eq!(MaskConfig::default().contour_approx, CHAIN_APPROX_SIMPLE);
+    }
+
+    #[test]
+    fn flags_override_environment_variables_which_override_defaults() {
+        env::set_var("MMN_MASK_MODE", "blur");
+        env::set_var("MMN_FUZZY", "0.8");
+        env::set_var("MMN_KEEP_GOING", "yes");
+        env::set_var("MMN_NO_CLOBBER", "0");
+        let flags = env_args();
+        env::set_var("MMN_NO_CLOBBER", "maybe");
+        let invalid = env_args();
+        for variable in ["MMN_MASK_MODE", "MMN_FUZZY", "MMN_KEEP_GOING", "MMN_NO_CLOBBER"] {
+            env::remove_var(variable);
+        }
+        let flags = flags.unwrap();
+        assert_eq!(flags, ["--mask-mode", "blur", "--fuzzy", "0.8", "--keep-going"]);
+        assert!(matches!(invalid, Err(MaskMyNameError::InvalidArgument(_))));
+        let command_line = ["--mask-mode", "pixelate", "list-langs"].map(String::from);
+        let cli = parse_arg_list(flags.into_iter().chain(command_line)).unwrap();
+        assert_eq!(cli.config.mask_mode, MaskMode::Pixelate);
+        assert_eq!(cli.config.match_mode, MatchMode::Fuzzy(0.8));
+        assert!(cli.keep_going);
+        assert!(!cli.no_clobber);
+        assert_eq!(cli.config.min_height, MaskConfig::default().min_height);
     }
 }
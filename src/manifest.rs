@@ -0,0 +1,119 @@
+//! Targets of individual images of a batch, read from a JSON or CSV file next to it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use csv::{ReaderBuilder, Trim};
+use serde_json::Value;
+
+use crate::MaskMyNameError;
+
+/// Image paths, resolved against `base`, with their targets, in the order the manifest names them.
+pub type Manifest = Vec<(PathBuf, Vec<String>)>;
+
+/// `{"file": "target"}` or `{"file": ["target", ...]}`.
+fn parse_json(contents: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let object = match serde_json::from_str(contents).map_err(|e| e.to_string())? {
+        Value::Object(object) => { object },
+        _ => { return Err("expected an object of file names".to_string()); }
+    };
+    object.into_iter().map(|(file, targets)| {
+        let targets = match targets {
+            Value::String(target) => { vec![target] },
+            Value::Array(targets) => {
+                targets.into_iter().map(|t| match t {
+                    Value::String(target) => { Ok(target) },
+                    _ => { Err(format!("{}: targets must be strings", file)) }
+                }).collect::<Result<_, _>>()?
+            },
+            _ => { return Err(format!("{}: expected a target or a list of them", file)); }
+        };
+        Ok((file, targets))
+    }).collect()
+}
+
+/// `file,target[,target...]` rows. A file given on several rows gets the targets of all of them.
+/// Blank lines and `#` comments are skipped.
+fn parse_csv(contents: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut reader = ReaderBuilder::new().has_headers(false).flexible(true).comment(Some(b'#')).trim(Trim::All)
+        .from_reader(contents.as_bytes());
+    let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let mut fields = record.iter();
+        let file = fields.next().filter(|f| !f.is_empty()).ok_or(format!("line {} has no file name", line))?;
+        let targets: Vec<String> = fields.map(str::to_string).collect();
+        if targets.is_empty() {
+            return Err(format!("line {} is not file,target", line));
+        }
+        match entries.iter_mut().find(|(f, _)| f == file) {
+            Some((_, known)) => { known.extend(targets); },
+            None => { entries.push((file.to_string(), targets)); }
+        }
+    }
+    Ok(entries)
+}
+
+/// Reads the manifest at `path`, JSON when it ends in `.json` and CSV otherwise, and resolves its file names against `base`,
+/// the input directory. Every file it names must exist and every target must be non-blank.
+pub fn load_manifest(path: &Path, base: &Path) -> Result<Manifest, MaskMyNameError> {
+    let invalid = |detail: String| MaskMyNameError::ManifestReadError(format!("{}: {}", path.display(), detail));
+    let contents = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let is_json = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.eq_ignore_ascii_case("json")).unwrap_or(false);
+    let entries = match is_json {
+        true => { parse_json(&contents) },
+        false => { parse_csv(&contents) }
+    }.map_err(invalid)?;
+    let missing: Vec<&str> = entries.iter().map(|(file, _)| file.as_str()).filter(|file| !base.join(file).is_file()).collect();
+    if !missing.is_empty() {
+        return Err(invalid(format!("no such file in {}: {}", base.display(), missing.join(", "))));
+    }
+    if let Some((file, _)) = entries.iter().find(|(_, targets)| targets.iter().any(|t| t.trim().is_empty())) {
+        return Err(invalid(format!("{}: empty target", file)));
+    }
+    Ok(entries.into_iter().map(|(file, targets)| (base.join(file), targets)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, targets: &[&str]) -> (String, Vec<String>) {
+        (file.to_string(), targets.iter().map(|t| t.to_string()).collect())
+    }
+
+    #[test]
+    fn json_manifests_take_a_target_or_a_list_of_them() {
+        let entries = parse_json(r#"{"alice.png": "Alice Smith", "bob.png": ["Bob Jones", "bjones"]}"#).unwrap();
+        assert_eq!(entries, vec![entry("alice.png", &["Alice Smith"]), entry("bob.png", &["Bob Jones", "bjones"])]);
+        for invalid in [r#"["alice.png"]"#, r#"{"alice.png": 3}"#, r#"{"alice.png": ["Alice", 3]}"#, "{"] {
+            assert!(parse_json(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn csv_manifests_merge_the_rows_of_a_repeated_file() {
+        let csv = "# screenshots\nalice.png, Alice Smith\n\nbob.png,Bob Jones,bjones\nalice.png,\"Smith, Alice\"\n";
+        let entries = parse_csv(csv).unwrap();
+        assert_eq!(entries, vec![entry("alice.png", &["Alice Smith", "Smith, Alice"]), entry("bob.png", &["Bob Jones", "bjones"])]);
+        assert_eq!(parse_csv("alice.png,Alice\nbob.png\n"), Err("line 2 is not file,target".to_string()));
+        assert!(parse_csv(",Alice\n").is_err());
+    }
+
+    #[test]
+    fn manifests_must_exist_and_name_existing_files_with_non_blank_targets() {
+        let base = std::env::temp_dir().join(format!("mask-my-name-manifest-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join("alice.png"), b"").unwrap();
+        let load = |contents: &str| {
+            let path = base.join("manifest.csv");
+            fs::write(&path, contents).unwrap();
+            load_manifest(&path, &base)
+        };
+        assert_eq!(load("alice.png,Alice Smith\n").unwrap(), vec![(base.join("alice.png"), vec!["Alice Smith".to_string()])]);
+        assert!(matches!(load("alice.png,Alice\nbob.png,Bob\n"), Err(MaskMyNameError::ManifestReadError(e)) if e.contains("bob.png")));
+        assert!(matches!(load("alice.png,Alice,\n"), Err(MaskMyNameError::ManifestReadError(e)) if e.contains("empty target")));
+        assert!(matches!(load_manifest(&base.join("missing.json"), &base), Err(MaskMyNameError::ManifestReadError(_))));
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! The union of masked regions as a COCO-style segmentation, for feeding masks to mask-aware training pipelines.
+
+use std::fs;
+use std::path::Path;
+use opencv::core::{Point, Rect, Scalar, CV_8UC1};
+use opencv::imgproc::{find_contours, rectangle, CHAIN_APPROX_SIMPLE, FILLED, LINE_8, RETR_EXTERNAL};
+use opencv::prelude::*;
+use opencv::types::VectorOfVectorOfPoint;
+use serde_json::json;
+
+use crate::{MaskedImage, MaskMyNameError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskEncoding {
+    /// Uncompressed run-length encoding in column-major order.
+    Rle,
+    /// Outer boundaries as flat `[x1, y1, x2, y2, ...]` lists.
+    Polygons,
+}
+
+/// Run lengths of the mask of `areas`, column by column from the top left, alternating unmasked and masked
+/// and starting with unmasked, so the first count is zero when pixel (0, 0) is masked. They add up to `width * height`.
+pub fn rle(areas: &[Rect], width: i32, height: i32) -> Vec<u32> {
+    let mut counts = Vec::new();
+    let (mut value, mut run) = (false, 0u32);
+    let mut push = |masked: bool, length: i32| {
+        if length <= 0 {
+            return;
+        }
+        if masked != value {
+            counts.push(run);
+            value = masked;
+            run = 0;
+        }
+        run += length as u32;
+    };
+    for x in 0..width {
+        let mut spans: Vec<(i32, i32)> = areas.iter()
+            .filter(|area| area.x <= x && x < area.x + area.width)
+            .map(|area| (area.y.max(0), (area.y + area.height).min(height)))
+            .filter(|(top, bottom)| top < bottom)
+            .collect();
+        spans.sort();
+        let mut y = 0;
+        for (top, bottom) in spans {
+            if bottom <= y {
+                continue;
+            }
+            push(false, top - y);
+            push(true, bottom - top.max(y));
+            y = bottom;
+        }
+        push(false, height - y);
+    }
+    counts.push(run);
+    counts
+}
+
+/// Outer boundaries of the mask of `areas`, as traced by `find_contours` through the boundary pixels with only
+/// the corners kept. Holes left between regions are not represented, use `rle` where they matter.
+pub fn polygons(areas: &[Rect], width: i32, height: i32) -> opencv::Result<Vec<Vec<i32>>> {
+    let mut mask = Mat::new_rows_cols_with_default(height, width, CV_8UC1, Scalar::all(0.))?;
+    for area in areas {
+        rectangle(&mut mask, *area, Scalar::all(255.), FILLED, LINE_8, 0)?;
+    }
+    let mut contours: VectorOfVectorOfPoint = Default::default();
+    find_contours(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE, Point::new(0, 0))?;
+    Ok(contours.iter().map(|contour| contour.iter().flat_map(|p| [p.x, p.y]).collect()).collect())
+}
+
+/// `{"size": [height, width], "counts": [...]}` or `{"size": [height, width], "polygons": [[...], ...]}`
+/// of what `masked` masked, QR codes included.
+pub fn segmentation(masked: &MaskedImage, encoding: MaskEncoding) -> Result<serde_json::Value, MaskMyNameError> {
+    let (width, height) = (masked.image.cols(), masked.image.rows());
+    Ok(match encoding {
+        MaskEncoding::Rle => { json!({ "size": [height, width], "counts": rle(&masked.masked, width, height) }) },
+        MaskEncoding::Polygons => {
+            let polygons = polygons(&masked.masked, width, height).map_err(|e| MaskMyNameError::MaskExportError(e.message))?;
+            json!({ "size": [height, width], "polygons": polygons })
+        }
+    })
+}
+
+pub fn write_segmentation(path: &str, masked: &MaskedImage, encoding: MaskEncoding) -> Result<(), MaskMyNameError> {
+    let segmentation = segmentation(masked, encoding)?;
+    fs::write(Path::new(path), format!("{}\n", segmentation)).map_err(|e| MaskMyNameError::MaskExportError(format!("{}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle_starts_with_an_empty_run_when_the_first_pixel_is_masked() {
+        // 3 columns of 2 rows: the top left pixel masked, then the whole second column.
+        assert_eq!(rle(&[Rect::new(0, 0, 1, 1)], 3, 2), [0, 1, 5]);
+        assert_eq!(rle(&[Rect::new(0, 0, 1, 1), Rect::new(1, 0, 1, 2)], 3, 2), [0, 1, 1, 2, 2]);
+        assert_eq!(rle(&[], 3, 2), [6]);
+        assert_eq!(rle(&[Rect::new(0, 0, 3, 2)], 3, 2), [0, 6]);
+    }
+
+    #[test]
+    fn rle_counts_overlapping_regions_once() {
+        let overlapping = [Rect::new(1, 1, 2, 3), Rect::new(2, 2, 2, 3)];
+        let counts = rle(&overlapping, 5, 6);
+        assert_eq!(counts, [7, 3, 3, 4, 3, 3, 7]);
+        assert_eq!(counts.iter().sum::<u32>(), 30);
+        // masked runs add up to the union, 6 + 6 - 2.
+        assert_eq!(counts.iter().skip(1).step_by(2).sum::<u32>(), 10);
+    }
+
+    #[test]
+    fn rle_clips_regions_at_the_edges() {
+        let clipped = [Rect::new(-2, -1, 3, 2), Rect::new(3, 2, 5, 5)];
+        let counts = rle(&clipped, 4, 3);
+        assert_eq!(counts, [0, 1, 10, 1]);
+        assert_eq!(counts.iter().sum::<u32>(), 12);
+    }
+
+    #[test]
+    fn polygons_trace_the_corners_of_each_area() {
+        let mut traced = polygons(&[Rect::new(1, 1, 3, 2), Rect::new(6, 0, 2, 2)], 10, 5).unwrap();
+        traced.sort();
+        assert_eq!(traced, [vec![1, 1, 1, 2, 3, 2, 3, 1], vec![6, 0, 6, 1, 7, 1, 7, 0]]);
+    }
+}
@@ -0,0 +1,137 @@
+//! EXIF orientation, which `imread` with `IMREAD_UNCHANGED` ignores, applied to the decoded image.
+
+use opencv::core::{flip, rotate, transpose, ROTATE_180, ROTATE_90_CLOCKWISE, ROTATE_90_COUNTERCLOCKWISE};
+use opencv::prelude::*;
+
+use crate::color_profile::{jpeg_segments, png_chunks, JPEG_SOI, PNG_SIGNATURE};
+
+const JPEG_APP1: u8 = 0xe1;
+const EXIF_MARKER: &[u8] = b"Exif\0\0";
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// The Orientation entry of IFD0 of a TIFF structure, in either byte order.
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => { true },
+        b"II" => { false },
+        _ => { return None; }
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?, *tiff.get(offset + 2)?, *tiff.get(offset + 3)?];
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    let ifd = u32_at(4)? as usize;
+    (0..u16_at(ifd)? as usize)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|entry| u16_at(*entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+}
+
+/// Orientation (1-8) from the Exif data of an encoded JPEG or PNG, `None` when there is none.
+pub fn exif_orientation(encoded: &[u8]) -> Option<u16> {
+    if encoded.starts_with(JPEG_SOI) {
+        jpeg_segments(encoded).into_iter()
+            .filter(|(marker, _)| *marker == JPEG_APP1)
+            .filter_map(|(_, segment)| segment.get(4..)?.strip_prefix(EXIF_MARKER))
+            .find_map(tiff_orientation)
+    } else if encoded.starts_with(PNG_SIGNATURE) {
+        // chunk data sits between the length and type, and the CRC.
+        png_chunks(encoded).into_iter()
+            .find(|(kind, _)| *kind == b"eXIf")
+            .and_then(|(_, chunk)| tiff_orientation(&chunk[8..chunk.len() - 4]))
+    } else {
+        None
+    }
+}
+
+/// Rotates and flips `image` so that an image tagged with `orientation` comes out upright.
+/// 1 and unknown values leave it as it is.
+pub fn upright(image: Mat, orientation: u16) -> opencv::Result<Mat> {
+    let mut turned: Mat = Default::default();
+    match orientation {
+        2 => { flip(&image, &mut turned, 1)?; },
+        3 => { rotate(&image, &mut turned, ROTATE_180)?; },
+        4 => { flip(&image, &mut turned, 0)?; },
+        5 => { transpose(&image, &mut turned)?; },
+        6 => { rotate(&image, &mut turned, ROTATE_90_CLOCKWISE)?; },
+        7 => {
+            let mut transposed: Mat = Default::default();
+            transpose(&image, &mut transposed)?;
+            rotate(&transposed, &mut turned, ROTATE_180)?;
+        },
+        8 => { rotate(&image, &mut turned, ROTATE_90_COUNTERCLOCKWISE)?; },
+        _ => { return Ok(image); }
+    }
+    Ok(turned)
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::{Scalar, CV_8UC1};
+    use super::*;
+
+    /// IFD0 with the Orientation entry after `other_entries` unrelated ones, big endian for `MM`, little for `II`.
+    fn tiff(byte_order: &[u8; 2], other_entries: u16, orientation: u16) -> Vec<u8> {
+        let big_endian = byte_order == b"MM";
+        let u16_bytes = |value: u16| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        let u32_bytes = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        let mut tiff = byte_order.to_vec();
+        tiff.extend(u16_bytes(42));
+        tiff.extend(u32_bytes(8));
+        tiff.extend(u16_bytes(other_entries + 1));
+        for (tag, value) in (0..other_entries).map(|_| (0x010f, 0)).chain([(ORIENTATION_TAG, orientation)]) {
+            tiff.extend(u16_bytes(tag));
+            tiff.extend(u16_bytes(3));
+            tiff.extend(u32_bytes(1));
+            tiff.extend(u16_bytes(value));
+            tiff.extend([0, 0]);
+        }
+        tiff.extend(u32_bytes(0));
+        tiff
+    }
+
+    #[test]
+    fn orientation_is_read_in_both_byte_orders() {
+        assert_eq!(tiff_orientation(&tiff(b"MM", 0, 6)), Some(6));
+        assert_eq!(tiff_orientation(&tiff(b"II", 0, 6)), Some(6));
+        assert_eq!(tiff_orientation(&tiff(b"II", 2, 8)), Some(8));
+        assert_eq!(tiff_orientation(&tiff(b"XX", 0, 6)), None);
+        // cut inside the entry: no orientation, and no panic.
+        let whole = tiff(b"MM", 1, 3);
+        assert_eq!(tiff_orientation(&whole[..whole.len() - 8]), None);
+    }
+
+    #[test]
+    fn short_app1_segments_are_skipped() {
+        let exif = [&[0xff, JPEG_APP1, 0, 0][..], EXIF_MARKER, &tiff(b"MM", 0, 3)].concat();
+        let length = ((exif.len() - 2) as u16).to_be_bytes();
+        let exif = [&exif[..2], &length[..], &exif[4..]].concat();
+        let jpeg = [JPEG_SOI, &[0xff, JPEG_APP1, 0, 2][..], &exif].concat();
+        assert_eq!(exif_orientation(&jpeg), Some(3));
+        assert_eq!(exif_orientation(&[JPEG_SOI, &[0xff, JPEG_APP1, 0, 2][..]].concat()), None);
+    }
+
+    #[test]
+    fn every_orientation_comes_out_upright() {
+        // 2 rows of 3, numbered row by row: 0 1 2 / 3 4 5.
+        let mut image = Mat::new_rows_cols_with_default(2, 3, CV_8UC1, Scalar::all(0.)).unwrap();
+        for index in 0..6 {
+            *image.at_2d_mut::<u8>(index / 3, index % 3).unwrap() = index as u8;
+        }
+        // columns, rows and the values of the first row after turning.
+        let expected: [(u16, i32, i32, &[u8]); 9] = [
+            (1, 3, 2, &[0, 1, 2]), (2, 3, 2, &[2, 1, 0]), (3, 3, 2, &[5, 4, 3]), (4, 3, 2, &[3, 4, 5]),
+            (5, 2, 3, &[0, 3]), (6, 2, 3, &[3, 0]), (7, 2, 3, &[5, 2]), (8, 2, 3, &[2, 5]), (9, 3, 2, &[0, 1, 2]),
+        ];
+        for (orientation, cols, rows, first_row) in expected {
+            let turned = upright(image.try_clone().unwrap(), orientation).unwrap();
+            assert_eq!((turned.cols(), turned.rows()), (cols, rows), "{}", orientation);
+            let row: Vec<u8> = (0..cols).map(|col| *turned.at_2d::<u8>(0, col).unwrap()).collect();
+            assert_eq!(row, first_row, "{}", orientation);
+        }
+    }
+}
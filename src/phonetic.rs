@@ -0,0 +1,76 @@
+//! Matching by pronunciation, so "Smyth" is read as "Smith".
+
+/// Soundex digit of an ASCII letter, `None` for vowels and `h`, `w`, `y`.
+fn code(c: char) -> Option<char> {
+    match c {
+        'b' | 'f' | 'p' | 'v' => { Some('1') },
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => { Some('2') },
+        'd' | 't' => { Some('3') },
+        'l' => { Some('4') },
+        'm' | 'n' => { Some('5') },
+        'r' => { Some('6') },
+        _ => { None }
+    }
+}
+
+/// American Soundex of the ASCII letters of `word`, e.g. `S530` for both "smith" and "smyth".
+/// `None` when the word has no ASCII letter.
+pub fn soundex(word: &str) -> Option<String> {
+    let mut letters = word.chars().filter(|c| c.is_ascii_alphabetic()).map(|c| c.to_ascii_lowercase());
+    let first = letters.next()?;
+    let mut encoded = first.to_ascii_uppercase().to_string();
+    let mut previous = code(first);
+    for c in letters {
+        let digit = code(c);
+        if digit.is_some() && digit != previous {
+            encoded.extend(digit);
+            if encoded.len() == 4 {
+                return Some(encoded);
+            }
+        }
+        // h and w do not separate letters of the same code, vowels do.
+        if c != 'h' && c != 'w' {
+            previous = digit;
+        }
+    }
+    Some(format!("{:0<4}", encoded))
+}
+
+fn codes(text: &str) -> Vec<String> {
+    text.split_whitespace().filter_map(soundex).collect()
+}
+
+/// Whether the words of `phrase` appear in `text`, in order and next to each other, by their Soundex codes.
+pub fn sounds_like(text: &str, phrase: &str) -> bool {
+    let phrase = codes(phrase);
+    !phrase.is_empty() && codes(text).windows(phrase.len()).any(|window| window == phrase.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_of_a_name_share_a_code() {
+        assert_eq!(soundex("Smith").as_deref(), Some("S530"));
+        assert_eq!(soundex("Smyth").as_deref(), Some("S530"));
+        assert_eq!(soundex("Lee").as_deref(), Some("L000"));
+        assert_eq!(soundex("1234"), None);
+    }
+
+    #[test]
+    fn h_and_w_do_not_separate_letters_of_the_same_code() {
+        // s and c are both 2, with only an h between them.
+        assert_eq!(soundex("Ashcraft").as_deref(), Some("A261"));
+        // a vowel between two 2s keeps both.
+        assert_eq!(soundex("Tymczak").as_deref(), Some("T522"));
+    }
+
+    #[test]
+    fn phrases_match_consecutive_words_in_order() {
+        assert!(sounds_like("signed by jane smyth today", "Jane Smith"));
+        assert!(!sounds_like("smyth signed for jane", "Jane Smith"));
+        assert!(!sounds_like("jane signed smyth", "Jane Smith"));
+        assert!(!sounds_like("jane smyth", ""));
+    }
+}
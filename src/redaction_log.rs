@@ -0,0 +1,114 @@
+//! Records of what was masked, for compliance logs in CSV or SQLite, and of everything OCR read, for refining targets.
+
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use csv::WriterBuilder;
+use opencv::core::Rect;
+
+use crate::{CodeMatch, MaskedImage, MaskMyNameError, RegionMatch};
+
+const OCR_DUMP_HEADER: [&str; 7] = ["file", "x", "y", "width", "height", "text", "matched"];
+
+const CSV_HEADER: [&str; 9] = ["file", "x", "y", "width", "height", "text", "target", "confidence", "timestamp"];
+
+/// Target column of the rows for QR codes, whose text column holds the payload.
+const QR_TARGET: &str = "qr-code";
+
+fn row(file_name: &str, area: &Rect, text: &str, target: &str, confidence: f64, timestamp: &str) -> [String; 9] {
+    [file_name.to_string(), area.x.to_string(), area.y.to_string(), area.width.to_string(), area.height.to_string(),
+     text.to_string(), target.to_string(), format!("{:.2}", confidence), timestamp.to_string()]
+}
+
+/// Appends one row per matched region and QR code of `masked` to the CSV at `csv_path`,
+/// writing the header first when the file is new or empty. The timestamp is in Unix seconds.
+pub fn append_csv(csv_path: &Path, image_path: &Path, masked: &MaskedImage) -> Result<(), MaskMyNameError> {
+    append_csv_rows(csv_path, &image_path.display().to_string(), &masked.matches, &masked.codes)
+}
+
+/// `append_csv` of matches that are not in a `MaskedImage`, e.g. those of an animation frame, with `file_name` as the file column.
+pub fn append_csv_rows(csv_path: &Path, file_name: &str, matches: &[RegionMatch], codes: &[CodeMatch]) -> Result<(), MaskMyNameError> {
+    let csv_error = |e: csv::Error| MaskMyNameError::CsvWriteError(e.to_string());
+    let file = OpenOptions::new().create(true).append(true).open(csv_path)
+        .map_err(|e| MaskMyNameError::CsvWriteError(e.to_string()))?;
+    let is_new = file.metadata().map(|m| m.len() == 0).unwrap_or(true);
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(file);
+    if is_new {
+        writer.write_record(CSV_HEADER).map_err(csv_error)?;
+    }
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0).to_string();
+    for m in matches {
+        writer.write_record(row(file_name, &m.area, &m.text, &m.target, m.score, &timestamp)).map_err(csv_error)?;
+    }
+    for code in codes {
+        writer.write_record(row(file_name, &code.area, &code.payload, QR_TARGET, 1., &timestamp)).map_err(csv_error)?;
+    }
+    writer.flush().map_err(|e| MaskMyNameError::CsvWriteError(e.to_string()))
+}
+
+/// Appends one row per region OCR'd in `masked`, matched or not, with the text as read, to the CSV at `dump_path`,
+/// writing the header first when the file is new or empty. `matched` is `true` for regions that matched a target.
+pub fn append_ocr_dump(dump_path: &Path, image_path: &Path, masked: &MaskedImage) -> Result<(), MaskMyNameError> {
+    let csv_error = |e: csv::Error| MaskMyNameError::CsvWriteError(e.to_string());
+    let file = OpenOptions::new().create(true).append(true).open(dump_path)
+        .map_err(|e| MaskMyNameError::CsvWriteError(e.to_string()))?;
+    let is_new = file.metadata().map(|m| m.len() == 0).unwrap_or(true);
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(file);
+    if is_new {
+        writer.write_record(OCR_DUMP_HEADER).map_err(csv_error)?;
+    }
+    let file_name = image_path.display().to_string();
+    for (area, text) in &masked.regions {
+        let matched = masked.matches.iter().any(|m| m.area == *area);
+        writer.write_record([file_name.clone(), area.x.to_string(), area.y.to_string(), area.width.to_string(), area.height.to_string(),
+                             text.trim().to_string(), matched.to_string()]).map_err(csv_error)?;
+    }
+    writer.flush().map_err(|e| MaskMyNameError::CsvWriteError(e.to_string()))
+}
+
+#[cfg(feature = "sqlite")]
+const DB_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS redactions (
+    id INTEGER PRIMARY KEY,
+    file TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    target TEXT NOT NULL,
+    confidence REAL NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS redactions_file ON redactions (file);";
+
+/// Inserts the rows `append_csv` would write into the `redactions` table of the SQLite database at `db_path`,
+/// creating the file and the table when missing. One transaction per image, so an image is recorded in full or not at all.
+#[cfg(feature = "sqlite")]
+pub fn append_db(db_path: &Path, image_path: &Path, masked: &MaskedImage) -> Result<(), MaskMyNameError> {
+    append_db_rows(db_path, &[(image_path.display().to_string(), &masked.matches, &masked.codes)])
+}
+
+/// `append_db` of matches that are not in a `MaskedImage`, each with the file column it is recorded under,
+/// e.g. the frames of an animation. All of them go in one transaction.
+#[cfg(feature = "sqlite")]
+pub fn append_db_rows(db_path: &Path, records: &[(String, &[RegionMatch], &[CodeMatch])]) -> Result<(), MaskMyNameError> {
+    let db_error = |e: rusqlite::Error| MaskMyNameError::DbWriteError(format!("{}: {}", db_path.display(), e));
+    let mut connection = rusqlite::Connection::open(db_path).map_err(db_error)?;
+    connection.execute_batch(DB_SCHEMA).map_err(db_error)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let transaction = connection.transaction().map_err(db_error)?;
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO redactions (file, x, y, width, height, text, target, confidence, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+        ).map_err(db_error)?;
+        for (file_name, matches, codes) in records {
+            let rows = matches.iter().map(|m| (&m.area, m.text.as_str(), m.target.as_str(), m.score))
+                .chain(codes.iter().map(|code| (&code.area, code.payload.as_str(), QR_TARGET, 1.)));
+            for (area, text, target, confidence) in rows {
+                insert.execute(rusqlite::params![file_name, area.x, area.y, area.width, area.height, text, target, confidence, timestamp])
+                    .map_err(db_error)?;
+            }
+        }
+    }
+    transaction.commit().map_err(db_error)
+}
@@ -0,0 +1,70 @@
+//! User supplied match logic as a Rhai script, deciding per region whether to mask it.
+
+use std::cell::RefCell;
+use opencv::core::Rect;
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+
+use crate::MaskMyNameError;
+
+/// Operations a script may run per region before it is stopped, so a runaway loop fails the image instead of hanging it.
+const MAX_OPERATIONS: u64 = 100_000;
+
+thread_local! {
+    /// The engine and the last compiled source with its AST, so a run compiles its script once instead of per region.
+    static COMPILED: RefCell<(Engine, Option<(String, AST)>)> = RefCell::new((new_engine(), None));
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine
+}
+
+/// Runs `source` with the region in scope and returns what it evaluates to, which must be a bool.
+/// The variables are `text` (normalized OCR text), `x`, `y`, `width`, `height`, `confidence`
+/// (Tesseract's mean word confidence, 0-100) and `targets`, the names of the targets of this pass.
+pub fn matches(source: &str, text: &str, area: Rect, confidence: i32, targets: &[String]) -> Result<bool, MaskMyNameError> {
+    COMPILED.with(|compiled| {
+        let (engine, cached) = &mut *compiled.borrow_mut();
+        if cached.as_ref().map(|(cached_source, _)| cached_source != source).unwrap_or(true) {
+            let ast = engine.compile(source).map_err(|e| MaskMyNameError::ScriptError(e.to_string()))?;
+            *cached = Some((source.to_string(), ast));
+        }
+        let ast = cached.as_ref().map(|(_, ast)| ast).ok_or_else(|| MaskMyNameError::ScriptError("no compiled script".to_string()))?;
+        let mut scope = Scope::new();
+        scope.push("text", text.to_string());
+        scope.push("x", area.x as i64);
+        scope.push("y", area.y as i64);
+        scope.push("width", area.width as i64);
+        scope.push("height", area.height as i64);
+        scope.push("confidence", confidence as i64);
+        scope.push("targets", targets.iter().map(|t| Dynamic::from(t.clone())).collect::<Array>());
+        engine.eval_ast_with_scope::<bool>(&mut scope, ast).map_err(|e| MaskMyNameError::ScriptError(e.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_see_every_documented_variable() {
+        let area = Rect::new(10, 20, 300, 40);
+        let targets = vec!["Jane Doe".to_string()];
+        let script = |source: &str| matches(source, "mail jane@example.com", area, 87, &targets);
+        assert_eq!(script(r#"text.contains("@")"#), Ok(true));
+        assert_eq!(script("x == 10 && y == 20 && width == 300 && height == 40"), Ok(true));
+        assert_eq!(script("confidence > 90"), Ok(false));
+        assert_eq!(script(r#"targets.len() == 1 && targets[0] == "Jane Doe""#), Ok(true));
+        // the cached AST is replaced when the source changes.
+        assert_eq!(script(r#"text.contains("@")"#), Ok(true));
+    }
+
+    #[test]
+    fn broken_and_runaway_scripts_fail() {
+        let script = |source: &str| matches(source, "", Rect::new(0, 0, 1, 1), 0, &[]);
+        assert!(matches!(script("text.len("), Err(MaskMyNameError::ScriptError(_))));
+        assert!(matches!(script("42"), Err(MaskMyNameError::ScriptError(_))));
+        assert!(matches!(script("loop {}"), Err(MaskMyNameError::ScriptError(_))));
+    }
+}
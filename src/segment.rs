@@ -0,0 +1,115 @@
+//! Splitting of words OCR ran together, e.g. "JaneDoe" or "janedoe", before matching.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::MaskMyNameError;
+
+/// One lowercased word per line, blank lines ignored.
+pub fn load_wordlist(path: &Path) -> Result<HashSet<String>, MaskMyNameError> {
+    let contents = fs::read_to_string(path).map_err(|_| MaskMyNameError::WordlistReadError(path.display().to_string()))?;
+    Ok(contents.lines().map(|line| line.trim().to_lowercase()).filter(|word| !word.is_empty()).collect())
+}
+
+/// A space before every uppercase letter that follows a lowercase one.
+fn split_camel_case(text: &str) -> String {
+    let mut split = String::with_capacity(text.len());
+    let mut previous_lower = false;
+    for c in text.chars() {
+        if previous_lower && c.is_uppercase() {
+            split.push(' ');
+        }
+        split.push(c);
+        previous_lower = c.is_lowercase();
+    }
+    split
+}
+
+/// The fewest words from `words` that exactly make up `run`, or `None` if it cannot be covered.
+fn split_run(run: &str, words: &HashSet<String>) -> Option<Vec<String>> {
+    let chars: Vec<char> = run.chars().collect();
+    // best[i] is the word count and start of the last word of the best split of the first i chars.
+    let mut best: Vec<Option<(usize, usize)>> = vec![None; chars.len() + 1];
+    best[0] = Some((0, 0));
+    for end in 1..=chars.len() {
+        for start in 0..end {
+            let Some((count, _)) = best[start] else { continue; };
+            let word: String = chars[start..end].iter().collect();
+            if words.contains(&word) && best[end].map(|(c, _)| count + 1 < c).unwrap_or(true) {
+                best[end] = Some((count + 1, start));
+            }
+        }
+    }
+    best[chars.len()]?;
+    let mut pieces = Vec::new();
+    let mut end = chars.len();
+    while end > 0 {
+        let (_, start) = best[end]?;
+        pieces.push(chars[start..end].iter().collect());
+        end = start;
+    }
+    pieces.reverse();
+    Some(pieces)
+}
+
+/// Splits camelCase, then every alphabetic run that is not itself a word but can be made of
+/// words from `words`. Runs that cannot be covered are left as they are.
+pub fn segment(text: &str, words: &HashSet<String>) -> String {
+    let text = split_camel_case(text);
+    if words.is_empty() {
+        return text;
+    }
+    let mut segmented = String::with_capacity(text.len());
+    let mut run = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_alphabetic() {
+            run.push(c);
+            continue;
+        }
+        let lower = run.to_lowercase();
+        match words.contains(&lower) {
+            true => { segmented.push_str(&run); },
+            false => match split_run(&lower, words) {
+                Some(pieces) => { segmented.push_str(&pieces.join(" ")); },
+                None => { segmented.push_str(&run); }
+            }
+        }
+        run.clear();
+        segmented.push(c);
+    }
+    segmented.pop();
+    segmented
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(list: &[&str]) -> HashSet<String> {
+        list.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn camel_case_is_split_without_a_wordlist() {
+        assert_eq!(segment("signed JaneDoe", &HashSet::new()), "signed Jane Doe");
+        assert_eq!(segment("ACME janedoe", &HashSet::new()), "ACME janedoe");
+    }
+
+    #[test]
+    fn runs_split_into_the_fewest_words() {
+        let words = words(&["jan", "ed", "oe", "jane", "doe"]);
+        assert_eq!(split_run("janedoe", &words), Some(vec!["jane".to_string(), "doe".to_string()]));
+        assert_eq!(split_run("janedoex", &words), None);
+        assert_eq!(split_run("", &words), Some(Vec::new()));
+    }
+
+    #[test]
+    fn lowercase_runs_are_split_with_a_wordlist() {
+        let words = words(&["jane", "doe", "signed"]);
+        assert_eq!(segment("janedoe signed.", &words), "jane doe signed.");
+        assert_eq!(segment("JaneDoe, janedoe", &words), "Jane Doe, jane doe");
+        // runs that cannot be covered are left alone.
+        assert_eq!(segment("janedoex", &words), "janedoex");
+    }
+}
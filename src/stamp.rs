@@ -0,0 +1,86 @@
+//! A visible notice such as "REDACTED" drawn into a corner of the masked image.
+
+use opencv::core::{add_weighted, Point, Rect, Scalar, Size};
+use opencv::imgproc::{get_text_size, put_text, FONT_HERSHEY_SIMPLEX, LINE_AA};
+use opencv::prelude::*;
+
+use crate::{depth_max_value, intersects, MaskMyNameError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+const CORNERS: [Corner; 4] = [Corner::BottomRight, Corner::BottomLeft, Corner::TopRight, Corner::TopLeft];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stamp {
+    pub text: String,
+    /// Where it goes unless that covers a masked region, see `draw_stamp`.
+    pub corner: Corner,
+    /// Text colour as 8-bit R, G, B, scaled to the image depth when drawn.
+    pub color: [u8; 3],
+    /// Font scale of `FONT_HERSHEY_SIMPLEX`, 1.0 being about 22 px tall.
+    pub scale: f64,
+}
+
+impl Default for Stamp {
+    fn default() -> Self {
+        Stamp { text: "REDACTED".to_string(), corner: Corner::BottomRight, color: [255, 255, 255], scale: 1. }
+    }
+}
+
+/// Gap between the box and the image edge, and between the box edge and the text, at scale 1.
+const STAMP_PADDING: f64 = 8.;
+/// Opacity of the black box behind the text.
+const STAMP_BOX_OPACITY: f64 = 0.5;
+
+fn box_at(corner: Corner, size: Size, image: Size, padding: i32) -> Rect {
+    let x = match corner {
+        Corner::TopLeft | Corner::BottomLeft => { padding },
+        Corner::TopRight | Corner::BottomRight => { image.width - size.width - padding }
+    };
+    let y = match corner {
+        Corner::TopLeft | Corner::TopRight => { padding },
+        Corner::BottomLeft | Corner::BottomRight => { image.height - size.height - padding }
+    };
+    Rect::new(x, y, size.width, size.height)
+}
+
+/// Draws `stamp` on a semi-transparent black box into its corner of `image`. When that box would cover one of the
+/// `masked` regions, the first other corner that covers none is taken, bottom right to top left; when every corner does,
+/// the stamp stays in its own. A stamp larger than the image is not drawn.
+pub fn draw_stamp(image: &mut Mat, stamp: &Stamp, masked: &[Rect]) -> Result<(), MaskMyNameError> {
+    let stamp_error = |e: opencv::Error| MaskMyNameError::StampError(e.message);
+    let thickness = ((stamp.scale * 2.).round() as i32).max(1);
+    let padding = (STAMP_PADDING * stamp.scale).round() as i32;
+    let mut baseline = 0;
+    let text_size = get_text_size(&stamp.text, FONT_HERSHEY_SIMPLEX, stamp.scale, thickness, &mut baseline).map_err(stamp_error)?;
+    let size = Size::new(text_size.width + 2 * padding, text_size.height + baseline + 2 * padding);
+    let image_size = Size::new(image.cols(), image.rows());
+    if size.width + 2 * padding > image_size.width || size.height + 2 * padding > image_size.height {
+        return Ok(());
+    }
+    let corner = std::iter::once(stamp.corner).chain(CORNERS)
+        .find(|corner| !masked.iter().any(|area| intersects(area, &box_at(*corner, size, image_size, padding))))
+        .unwrap_or(stamp.corner);
+    let area = box_at(corner, size, image_size, padding);
+    let max = depth_max_value(image.depth());
+    {
+        let mut roi = Mat::roi(&*image, area).map_err(stamp_error)?;
+        let black = Mat::new_rows_cols_with_default(area.height, area.width, roi.typ(), Scalar::new(0., 0., 0., max)).map_err(stamp_error)?;
+        let mut dimmed: Mat = Default::default();
+        add_weighted(&roi, 1. - STAMP_BOX_OPACITY, &black, STAMP_BOX_OPACITY, 0., &mut dimmed, -1).map_err(stamp_error)?;
+        dimmed.copy_to(&mut roi).map_err(stamp_error)?;
+    }
+    let [r, g, b] = stamp.color.map(|c| c as f64 * max / 255.);
+    let color = match image.channels() {
+        1 | 2 => { Scalar::new(0.299 * r + 0.587 * g + 0.114 * b, max, 0., 0.) },
+        _ => { Scalar::new(b, g, r, max) }
+    };
+    let origin = Point::new(area.x + padding, area.y + padding + text_size.height);
+    put_text(image, &stamp.text, origin, FONT_HERSHEY_SIMPLEX, stamp.scale, color, thickness, LINE_AA, false).map_err(stamp_error)
+}
@@ -0,0 +1,47 @@
+//! Detected regions as an SVG overlay, for frontends that draw their own scalable redaction boxes.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use opencv::core::Rect;
+use opencv::prelude::*;
+
+use crate::{MaskedImage, MaskMyNameError};
+
+/// Matched regions and QR codes are filled, the others only outlined. Frontends can restyle the classes.
+const STYLE: &str = ".matched { fill: black; fill-opacity: 0.6; stroke: red; stroke-width: 2; } \
+                     .unmatched { fill: none; stroke: gray; stroke-width: 1; stroke-dasharray: 4 2; }";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn push_rect(svg: &mut String, area: &Rect, class: &str, title: &str) {
+    let _ = writeln!(svg, "  <rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"><title>{}</title></rect>",
+                     class, area.x, area.y, area.width, area.height, escape(title));
+}
+
+/// An SVG of the image's size with one `<rect>` per region of `masked`, its OCR text as `<title>` for hover tooltips.
+pub fn overlay(masked: &MaskedImage) -> String {
+    let (width, height) = (masked.image.cols(), masked.image.rows());
+    let mut svg = String::new();
+    let _ = writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+                     width, height, width, height);
+    let _ = writeln!(svg, "  <style>{}</style>", STYLE);
+    for (area, text) in &masked.regions {
+        let class = match masked.matches.iter().any(|m| m.area == *area) {
+            true => { "matched" },
+            false => { "unmatched" }
+        };
+        push_rect(&mut svg, area, class, text);
+    }
+    for code in &masked.codes {
+        push_rect(&mut svg, &code.area, "matched", &code.payload);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn write_overlay(svg_path: &str, masked: &MaskedImage) -> Result<(), MaskMyNameError> {
+    fs::write(Path::new(svg_path), overlay(masked)).map_err(|_| MaskMyNameError::SvgWriteError(svg_path.to_string()))
+}
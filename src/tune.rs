@@ -0,0 +1,134 @@
+//! Precision and recall of the current settings against images labelled with whether they show a target.
+
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::Path;
+use csv::{ReaderBuilder, Trim};
+
+use crate::{build_passes, load_image, mask_regions, stem_of, Coverage, MaskConfig, MaskMyNameError, MatchMode};
+
+/// Counts of expected against predicted matches over a labelled set.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Scores {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub true_negatives: usize,
+}
+
+impl Scores {
+    fn record(&mut self, expected: bool, predicted: bool) {
+        match (expected, predicted) {
+            (true, true) => { self.true_positives += 1; },
+            (false, true) => { self.false_positives += 1; },
+            (true, false) => { self.false_negatives += 1; },
+            (false, false) => { self.true_negatives += 1; }
+        }
+    }
+
+    /// Zero when nothing was predicted.
+    pub fn precision(&self) -> f64 {
+        match self.true_positives + self.false_positives {
+            0 => { 0. },
+            predicted => { self.true_positives as f64 / predicted as f64 }
+        }
+    }
+
+    /// Zero when nothing was expected.
+    pub fn recall(&self) -> f64 {
+        match self.true_positives + self.false_negatives {
+            0 => { 0. },
+            expected => { self.true_positives as f64 / expected as f64 }
+        }
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        match precision + recall {
+            sum if sum > 0. => { 2. * precision * recall / sum },
+            _ => { 0. }
+        }
+    }
+}
+
+/// `file,label` rows, the label being `yes`/`no`, `true`/`false` or `1`/`0`. Blank lines and `#` comments are skipped,
+/// a file name with a comma has to be quoted.
+fn parse_labels(contents: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut reader = ReaderBuilder::new().has_headers(false).flexible(true).comment(Some(b'#')).trim(Trim::All)
+        .from_reader(contents.as_bytes());
+    let mut labels = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let (file, label) = match (record.get(0), record.get(1), record.len()) {
+            (Some(file), Some(label), 2) if !file.is_empty() => { (file, label) },
+            _ => { return Err(format!("line {} is not file,label", line)); }
+        };
+        let expected = match label.to_lowercase().as_str() {
+            "yes" | "true" | "1" => { true },
+            "no" | "false" | "0" => { false },
+            other => { return Err(format!("line {}: unknown label {}", line, other)); }
+        };
+        labels.push((file.to_string(), expected));
+    }
+    Ok(labels)
+}
+
+/// The labels file at `path`, see `parse_labels`.
+pub fn load_labels(path: &Path) -> Result<Vec<(String, bool)>, MaskMyNameError> {
+    let invalid = |detail: String| MaskMyNameError::LabelsReadError(format!("{}: {}", path.display(), detail));
+    let contents = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    parse_labels(&contents).map_err(invalid)
+}
+
+/// Runs detection and matching on every labelled image of `dir`, nothing is written.
+/// Without `thresholds` the scores of `config` as it is come back, with `None` as threshold. Otherwise each image is
+/// matched once in fuzzy mode and scored for every threshold, an image counting as a match when its best region reaches it.
+pub fn tune(dir: &Path, labels: &[(String, bool)], target_strings: &[String], config: &MaskConfig,
+            thresholds: &[f64]) -> Result<Vec<(Option<f64>, Scores)>, MaskMyNameError> {
+    let mut config = config.clone();
+    config.crops_dir = None;
+    config.interactive = false;
+    config.verify = false;
+    if !thresholds.is_empty() {
+        config.match_mode = MatchMode::Fuzzy(0.);
+        config.max_matches = None;
+    }
+    let mut passes = build_passes(target_strings, &config)?;
+    let mut current = Scores::default();
+    let mut swept = vec![Scores::default(); thresholds.len()];
+    for (file, expected) in labels {
+        let image_path = dir.join(file);
+        let image = load_image(&image_path, &config)?;
+        let masked = mask_regions(&mut passes, image, stem_of(&image_path), &config, &mut |_, _| ControlFlow::Continue(()))?;
+        let predicted = match masked.coverage() {
+            Coverage::NoMatch => { false },
+            Coverage::Partial => { !config.require_all },
+            Coverage::Full => { true }
+        };
+        current.record(*expected, predicted);
+        let best = masked.matches.iter().map(|m| m.score).reduce(f64::max);
+        for (scores, threshold) in swept.iter_mut().zip(thresholds) {
+            scores.record(*expected, best.map(|score| score >= *threshold).unwrap_or(false));
+        }
+    }
+    Ok(match thresholds.is_empty() {
+        true => { vec![(None, current)] },
+        false => { thresholds.iter().map(|t| Some(*t)).zip(swept).collect() }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_read_as_csv() {
+        let labels = parse_labels("# file,label\nscan1.png,yes\n\n  scan2.png , NO\n\"a, b.png\",1\n").unwrap();
+        assert_eq!(labels, [("scan1.png".to_string(), true), ("scan2.png".to_string(), false), ("a, b.png".to_string(), true)]);
+        assert_eq!(parse_labels("scan1.png,maybe\n"), Err("line 1: unknown label maybe".to_string()));
+        assert_eq!(parse_labels("scan1.png,yes\nscan2.png\n"), Err("line 2 is not file,label".to_string()));
+        assert!(parse_labels("scan1.png,yes,no\n").is_err());
+        assert!(parse_labels(",yes\n").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! End to end runs of the binary on the images in `tests/fixtures`. They need Tesseract with the `eng` language pack,
+//! and `rus` for the mixed script image, so they only run with `cargo test -- --ignored`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use opencv::core::{absdiff, count_non_zero, Rect, CV_16U, CV_8U};
+use opencv::imgcodecs::{imread, IMREAD_UNCHANGED};
+use opencv::prelude::*;
+use mask_my_name::MaskMyNameError;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+/// Runs mask-my-name on the fixture `name` from an empty directory of its own, where the masked image is written.
+fn run(name: &str, args: &[&str]) -> (Output, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("mask-my-name-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_mask-my-name"))
+        .arg(fixture(name))
+        .args(args)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    (output, dir)
+}
+
+fn read(path: &Path) -> Mat {
+    imread(path.to_str().unwrap(), IMREAD_UNCHANGED).unwrap()
+}
+
+#[test]
+#[ignore = "needs tesseract eng"]
+fn masks_a_16bit_image_and_keeps_its_depth() {
+    let (output, dir) = run("16bit.png", &["Jane Doe"]);
+    assert!(output.status.success());
+    assert_eq!(read(&dir.join("16bit_masked.png")).depth(), CV_16U);
+}
+
+#[test]
+#[ignore = "needs tesseract eng"]
+fn masks_a_16bit_image_converted_to_8bit() {
+    let (output, dir) = run("16bit.png", &["Jane Doe", "--force-8bit"]);
+    assert!(output.status.success());
+    assert_eq!(read(&dir.join("16bit_masked.png")).depth(), CV_8U);
+}
+
+#[test]
+#[ignore = "needs tesseract eng"]
+fn masks_only_the_opaque_text_of_a_transparent_image() {
+    // the lower half is fully transparent, the "John Smith" its colour data still holds is not part of the image.
+    let (output, dir) = run("transparent.png", &["Jane Doe", "John Smith"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-> \"Jane Doe\""));
+    assert!(!stdout.contains("-> \"John Smith\""));
+    let (original, masked) = (read(&fixture("transparent.png")), read(&dir.join("transparent_masked.png")));
+    assert_eq!(masked.channels(), 4);
+    let lower_half = Rect::new(0, original.rows() / 2, original.cols(), original.rows() - original.rows() / 2);
+    let mut diff = Mat::default();
+    absdiff(&Mat::roi(&original, lower_half).unwrap(), &Mat::roi(&masked, lower_half).unwrap(), &mut diff).unwrap();
+    assert_eq!(count_non_zero(&diff.reshape(1, 0).unwrap()).unwrap(), 0);
+}
+
+#[test]
+#[ignore = "needs tesseract eng"]
+fn finds_no_text_under_a_transparent_area() {
+    let (output, dir) = run("transparent.png", &["John Smith"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&MaskMyNameError::NoMatchingString().to_string()));
+    assert!(!dir.join("transparent_masked.png").exists());
+}
+
+#[test]
+#[ignore = "needs tesseract eng+rus"]
+fn masks_a_target_and_a_script_of_a_mixed_script_image() {
+    // "Patient: Jane Doe" above, the Cyrillic "Врач: Иван Петров" below.
+    let (output, dir) = run("mixed_script.png", &["Jane Doe", "--lang", "eng+rus", "--mask-script", "cyrillic"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-> \"Jane Doe\""));
+    assert!(stdout.contains("-> \"script:Cyrillic\""));
+    let (original, masked) = (read(&fixture("mixed_script.png")), read(&dir.join("mixed_script_masked.png")));
+    let half = original.rows() / 2;
+    for rows in [Rect::new(0, 0, original.cols(), half), Rect::new(0, half, original.cols(), original.rows() - half)] {
+        let mut diff = Mat::default();
+        absdiff(&Mat::roi(&original, rows).unwrap(), &Mat::roi(&masked, rows).unwrap(), &mut diff).unwrap();
+        assert!(count_non_zero(&diff.reshape(1, 0).unwrap()).unwrap() > 0);
+    }
+}
+
+#[test]
+#[ignore = "needs tesseract eng"]
+fn finds_gradient_text_with_mser_only() {
+    // too bright for the HSV value threshold of the contour detector.
+    let (output, dir) = run("gradient_text.png", &["Jane Doe"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&MaskMyNameError::NoMatchingString().to_string()));
+    assert!(!dir.join("gradient_text_masked.png").exists());
+    let (output, dir) = run("gradient_text.png", &["Jane Doe", "--detector", "mser"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("-> \"Jane Doe\""));
+    assert!(dir.join("gradient_text_masked.png").exists());
+}